@@ -0,0 +1,59 @@
+//! Compare the `Error<T>` layouts with `cargo bench` and `cargo bench --features thin`
+
+use chainerror::Context as _;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::io;
+
+const LAYOUT: &str = if cfg!(feature = "thin") {
+    "thin"
+} else {
+    "inline"
+};
+
+#[derive(Debug)]
+enum ParseError {
+    Syntax(u32),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Syntax(line) => write!(f, "syntax error in line {}", line),
+        }
+    }
+}
+
+#[inline(never)]
+fn read(fail: bool) -> Result<u64, io::Error> {
+    if fail {
+        Err(io::Error::from(io::ErrorKind::NotFound))
+    } else {
+        Ok(black_box(42))
+    }
+}
+
+#[inline(never)]
+fn parse(fail: bool) -> chainerror::Result<u64, ParseError> {
+    read(fail).context(ParseError::Syntax(1))
+}
+
+#[inline(never)]
+fn load(fail: bool) -> chainerror::Result<u64, &'static str> {
+    parse(fail).context("loading the config")
+}
+
+fn bench_layout(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!(
+        "{} ({} bytes Result)",
+        LAYOUT,
+        std::mem::size_of::<chainerror::Result<u64, &'static str>>()
+    ));
+
+    group.bench_function("happy path", |b| b.iter(|| load(black_box(false))));
+    group.bench_function("error path", |b| b.iter(|| load(black_box(true))));
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_layout);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+//! Compare the eager `context(format!(..))` with the lazy `with_context(|| format!(..))`
+
+use chainerror::Context as _;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the allocations to show, that `with_context()` does not allocate on success
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[inline(never)]
+fn read(i: u32) -> Result<u32, io::Error> {
+    Ok(black_box(i))
+}
+
+fn eager(i: u32) -> chainerror::Result<u32, String> {
+    read(i).context(format!("reading record {}", i))
+}
+
+fn lazy(i: u32) -> chainerror::Result<u32, String> {
+    read(i).with_context(|| format!("reading record {}", i))
+}
+
+fn allocations(f: fn(u32) -> chainerror::Result<u32, String>) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for i in 0..1000 {
+        black_box(f(i)).unwrap();
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_with_context(c: &mut Criterion) {
+    assert_eq!(allocations(lazy), 0);
+    assert_eq!(allocations(eager), 1000);
+
+    let mut group = c.benchmark_group("success path");
+    group.bench_function("context", |b| b.iter(|| eager(black_box(42))));
+    group.bench_function("with_context", |b| b.iter(|| lazy(black_box(42))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_with_context);
+criterion_main!(benches);
@@ -25,7 +25,7 @@ fn func1() -> Result<(), Box<dyn Error + Send + Sync>> {
 fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     if let Err(e) = func1() {
         eprintln!("Error: {}", e);
-        let mut s: &(dyn Error) = e.as_ref();
+        let mut s: &dyn Error = e.as_ref();
         while let Some(c) = s.source() {
             if let Some(ioerror) = c.downcast_ref::<io::Error>() {
                 eprintln!("caused by: std::io::Error: {}", ioerror);
@@ -2,316 +2,2688 @@
 #![deny(clippy::all)]
 #![allow(clippy::needless_doctest_main)]
 #![deny(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use std::any::TypeId;
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+#[cfg(feature = "arc")]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+#[cfg(not(feature = "std"))]
+use core::error::Error as StdError;
+use core::fmt::{Debug, Display, Formatter};
+#[cfg(feature = "future")]
+use core::future::Future;
+use core::iter::FromIterator;
+use core::panic::Location;
+#[cfg(any(feature = "future", feature = "stream"))]
+use core::pin::Pin;
+#[cfg(any(feature = "future", feature = "stream"))]
+use core::task::Poll;
+#[cfg(feature = "stream")]
+use futures_core::Stream;
+#[cfg(feature = "backtrace")]
+use std::backtrace::{Backtrace, BacktraceStatus};
+#[cfg(feature = "std")]
 use std::error::Error as StdError;
-use std::fmt::{Debug, Display, Formatter};
-use std::panic::Location;
+#[cfg(feature = "thread")]
+use std::thread::{Thread, ThreadId};
+#[cfg(feature = "timestamp")]
+use std::time::{Instant, SystemTime};
+#[cfg(feature = "tracing")]
+use tracing_error::{SpanTrace, SpanTraceStatus};
+
+/// The error cause stored in an [`Error`](Error)
+///
+/// Dereferences to the `dyn Error` of the cause. With the `arc` feature, the cause is shared
+/// between the clones of an error.
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::Context as _;
+/// use std::io;
+///
+/// let err = Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+///     .context("reading")
+///     .unwrap_err();
+///
+/// let source = err.into_source().unwrap();
+/// assert_eq!(source.to_string(), "entity not found");
+/// assert!(source.is::<io::Error>());
+///
+/// let io_error = source.downcast::<io::Error>().unwrap();
+/// assert_eq!(io_error.kind(), io::ErrorKind::NotFound);
+/// ```
+pub struct Cause {
+    repr: CauseRepr,
+}
+
+enum CauseRepr {
+    #[cfg(not(feature = "arc"))]
+    Boxed(Box<dyn StdError + 'static + Send + Sync>),
+    #[cfg(feature = "arc")]
+    Shared(Arc<dyn StdError + 'static + Send + Sync>),
+}
+
+/// Storage for the typed attachments of an [`Error`](Error)
+#[cfg(not(feature = "arc"))]
+type Attachment = Box<dyn Any + Send + Sync>;
+#[cfg(feature = "arc")]
+type Attachment = Arc<dyn Any + Send + Sync>;
+
+/// Compile the items only with one of the features, which capture the environment of every
+/// [`Layer`](Layer), and the items after `else` only without them
+macro_rules! cfg_captured {
+    ({ $($item:item)* } else { $($else:item)* }) => {
+        $(
+            #[cfg(any(
+                feature = "backtrace",
+                feature = "timestamp",
+                feature = "thread",
+                feature = "tracing"
+            ))]
+            $item
+        )*
+        $(
+            #[cfg(not(any(
+                feature = "backtrace",
+                feature = "timestamp",
+                feature = "thread",
+                feature = "tracing"
+            )))]
+            $else
+        )*
+    };
+}
+
+cfg_captured! {
+    {
+        #[cfg(not(any(feature = "arc", feature = "thin")))]
+        type StoredCaptured = Box<Captured>;
+        // With the `thin` feature the `Layer` is already on the heap, so save the extra allocation
+        #[cfg(all(feature = "thin", not(feature = "arc")))]
+        type StoredCaptured = Captured;
+        #[cfg(feature = "arc")]
+        type StoredCaptured = Arc<Captured>;
+    } else {
+        // Nothing is captured, so store the empty `Captured` inline
+        type StoredCaptured = Captured;
+
+        #[cfg(feature = "arc")]
+        impl Clone for Captured {
+            #[inline]
+            fn clone(&self) -> Self {
+                Captured {}
+            }
+        }
+    }
+}
 
 /// chains an inner error kind `T` with a causing error
 pub struct Error<T> {
-    occurrence: Option<String>,
+    inner: Repr<T>,
+}
+
+/// The contents of an [`Error<T>`](Error)
+#[cfg_attr(feature = "arc", derive(Clone))]
+struct Inner<T> {
+    layer: Layer,
     kind: T,
-    error_cause: Option<Box<dyn StdError + 'static + Send + Sync>>,
 }
 
-/// convenience type alias
-pub type Result<O, E> = std::result::Result<O, Error<E>>;
+/// With the `thin` feature, [`Error<T>`](Error) is a single pointer to its [`Inner<T>`](Inner)
+#[cfg_attr(feature = "arc", derive(Clone))]
+struct Repr<T>(
+    #[cfg(not(feature = "thin"))] Inner<T>,
+    #[cfg(feature = "thin")] Box<Inner<T>>,
+);
 
-impl<T: 'static + Display + Debug> Error<T> {
-    /// Use the `context()` or `map_context()` Result methods instead of calling this directly
+/// The part of [`Error<T>`](Error), which does not depend on `T`
+#[cfg_attr(feature = "arc", derive(Clone))]
+struct Layer {
+    occurrence: Option<Occurrence>,
+    error_cause: Option<Cause>,
+    extras: Option<Box<Extras>>,
+    // only read with the features, which capture the environment
+    #[allow(dead_code)]
+    captured: StoredCaptured,
+}
+
+/// The environment captured with the optional features, when a [`Layer`](Layer) is created
+///
+/// Stored out of line to keep [`Error<T>`](Error) small. Empty without the features.
+// `std::backtrace` needs Rust 1.65
+#[cfg_attr(feature = "backtrace", clippy::msrv = "1.65")]
+struct Captured {
+    #[cfg(feature = "backtrace")]
+    backtrace: Backtrace,
+    #[cfg(feature = "timestamp")]
+    timestamp: SystemTime,
+    #[cfg(feature = "timestamp")]
+    instant: Instant,
+    #[cfg(feature = "thread")]
+    thread: Thread,
+    #[cfg(feature = "tracing")]
+    span_trace: SpanTrace,
+}
+
+#[cfg_attr(feature = "backtrace", clippy::msrv = "1.65")]
+impl Captured {
     #[inline]
-    pub fn new(
-        kind: T,
-        error_cause: Option<Box<dyn StdError + 'static + Send + Sync>>,
-        occurrence: Option<String>,
-    ) -> Self {
+    fn now() -> Self {
         Self {
-            occurrence,
-            kind,
-            error_cause,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture(),
+            #[cfg(feature = "timestamp")]
+            timestamp: SystemTime::now(),
+            #[cfg(feature = "timestamp")]
+            instant: Instant::now(),
+            #[cfg(feature = "thread")]
+            thread: std::thread::current(),
+            #[cfg(feature = "tracing")]
+            span_trace: SpanTrace::capture(),
         }
     }
+}
 
-    /// return the root cause of the error chain, if any exists
-    pub fn root_cause(&self) -> Option<&(dyn StdError + 'static)> {
-        self.iter().last()
+/// The optional parts of a [`Layer`](Layer), only allocated when used
+#[derive(Default)]
+#[cfg_attr(feature = "arc", derive(Clone))]
+struct Extras {
+    fields: Vec<Field>,
+    attachments: Vec<Attachment>,
+    hints: Vec<Hint>,
+    suppressed: Vec<Cause>,
+}
+
+impl Cause {
+    /// Box `error` as error cause
+    #[inline]
+    fn from_error<E: Into<Box<dyn StdError + 'static + Send + Sync>>>(error: E) -> Self {
+        Self::new(error.into())
     }
 
-    /// Find the first error cause of type U, if any exists
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// use chainerror::Context as _;
-    /// use chainerror::ErrorDown as _;
-    /// use std::error::Error;
-    /// use std::io;
-    ///
-    /// fn do_some_io() -> Result<(), Box<dyn Error + Send + Sync>> {
-    ///     Err(io::Error::from(io::ErrorKind::NotFound))?;
-    ///     Ok(())
-    /// }
-    ///
-    /// chainerror::str_context!(Func2Error);
-    ///
-    /// fn func2() -> Result<(), Box<dyn Error + Send + Sync>> {
-    ///     let filename = "foo.txt";
-    ///     do_some_io().context(Func2Error(format!("Error reading '{}'", filename)))?;
-    ///     Ok(())
-    /// }
-    ///
-    /// chainerror::str_context!(Func1Error);
-    ///
-    /// fn func1() -> Result<(), Box<dyn Error + Send + Sync>> {
-    ///     func2().context(Func1Error::new("func1 error"))?;
-    ///     Ok(())
-    /// }
-    ///
-    /// if let Err(e) = func1() {
-    ///     if let Some(f1err) = e.downcast_chain_ref::<Func1Error>() {
-    ///         assert!(f1err.find_cause::<io::Error>().is_some());
-    ///
-    ///         assert!(f1err.find_chain_cause::<Func2Error>().is_some());
-    ///     }
-    /// #        else {
-    /// #            panic!();
-    /// #        }
-    /// }
-    /// #    else {
-    /// #         unreachable!();
-    /// #    }
-    /// ```
+    /// Store the boxed `error`, unless it is a `Cause` itself
     #[inline]
-    pub fn find_cause<U: StdError + 'static>(&self) -> Option<&U> {
-        self.iter()
-            .filter_map(<dyn StdError>::downcast_ref::<U>)
-            .next()
+    // `Box` is converted to `Arc` with the `arc` feature
+    #[allow(clippy::useless_conversion)]
+    fn new(error: Box<dyn StdError + 'static + Send + Sync>) -> Self {
+        match error.downcast::<Cause>() {
+            Ok(cause) => *cause,
+            #[cfg(not(feature = "arc"))]
+            Err(error) => Cause {
+                repr: CauseRepr::Boxed(error),
+            },
+            #[cfg(feature = "arc")]
+            Err(error) => Cause {
+                repr: CauseRepr::Shared(error.into()),
+            },
+        }
     }
 
-    /// Find the first error cause of type [`Error<U>`](Error), if any exists
-    ///
-    /// Same as `find_cause`, but hides the [`Error<U>`](Error) implementation internals
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// # chainerror::str_context!(FooError);
-    /// # let err = chainerror::Error::new(String::new(), None, None);
-    /// // Instead of writing
-    /// err.find_cause::<chainerror::Error<FooError>>();
+    /// Attempt to move the cause out as the concrete type `E`
     ///
-    /// // leave out the chainerror::Error<FooError> implementation detail
-    /// err.find_chain_cause::<FooError>();
-    /// ```
+    /// With the `arc` feature, this fails, if the cause is still shared with a clone of the
+    /// error.
+    pub fn downcast<E: StdError + 'static + Send + Sync>(self) -> core::result::Result<E, Self> {
+        match self.repr {
+            #[cfg(not(feature = "arc"))]
+            CauseRepr::Boxed(error) => error.downcast::<E>().map(|e| *e).map_err(|error| Cause {
+                repr: CauseRepr::Boxed(error),
+            }),
+            #[cfg(feature = "arc")]
+            CauseRepr::Shared(error) => {
+                if !error.is::<E>() {
+                    return Err(Cause {
+                        repr: CauseRepr::Shared(error),
+                    });
+                }
+                // SAFETY: the `Arc` was checked to contain an `E`
+                let error = unsafe { Arc::from_raw(Arc::into_raw(error) as *const E) };
+                Arc::try_unwrap(error).map_err(|error| Cause {
+                    repr: CauseRepr::Shared(error),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(feature = "arc")]
+impl Clone for Cause {
     #[inline]
-    pub fn find_chain_cause<U: StdError + 'static>(&self) -> Option<&Error<U>> {
-        self.iter()
-            .filter_map(<dyn StdError>::downcast_ref::<Error<U>>)
-            .next()
+    fn clone(&self) -> Self {
+        match &self.repr {
+            CauseRepr::Shared(error) => Cause {
+                repr: CauseRepr::Shared(error.clone()),
+            },
+        }
     }
+}
+
+impl core::ops::Deref for Cause {
+    type Target = dyn StdError + 'static;
 
-    /// Find the first error cause of type [`Error<U>`](Error) or `U`, if any exists and return `U`
-    ///
-    /// Same as `find_cause` and `find_chain_cause`, but hides the [`Error<U>`](Error) implementation internals
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// # chainerror::str_context!(FooErrorKind);
-    /// # let err = chainerror::Error::new(String::new(), None, None);
-    /// // Instead of writing
-    /// err.find_cause::<chainerror::Error<FooErrorKind>>();
-    /// // and/or
-    /// err.find_chain_cause::<FooErrorKind>();
-    /// // and/or
-    /// err.find_cause::<FooErrorKind>();
-    ///
-    /// // leave out the chainerror::Error<FooErrorKind> implementation detail
-    /// err.find_kind_or_cause::<FooErrorKind>();
-    /// ```
     #[inline]
-    pub fn find_kind_or_cause<U: StdError + 'static>(&self) -> Option<&U> {
-        self.iter()
-            .filter_map(|e| {
-                e.downcast_ref::<Error<U>>()
-                    .map(|e| e.kind())
-                    .or_else(|| e.downcast_ref::<U>())
-            })
-            .next()
+    fn deref(&self) -> &Self::Target {
+        match &self.repr {
+            #[cfg(not(feature = "arc"))]
+            CauseRepr::Boxed(error) => &**error,
+            #[cfg(feature = "arc")]
+            CauseRepr::Shared(error) => &**error,
+        }
     }
+}
+
+impl StdError for Cause {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        (**self).source()
+    }
+
+    #[allow(deprecated)]
+    #[inline]
+    fn cause(&self) -> Option<&dyn StdError> {
+        (**self).cause()
+    }
+}
+
+impl Display for Cause {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl Debug for Cause {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// Return the [`Inner<T>`](Inner) of `error` as `dyn Error`, if it is an [`Error<T>`](Error) of
+/// any `T` or wraps one
+///
+/// The deprecated `cause()` of `Error<T>` returns its [`Repr<T>`](Repr), whose `source()` is the
+/// `Inner<T>`, whose `source()` is the [`Layer`](Layer). A `Repr<T>` can't be returned as
+/// `source()` of another error, so no other error is mistaken for an `Error<T>`.
+#[allow(deprecated)]
+#[inline]
+fn inner_of(error: &dyn StdError) -> Option<&(dyn StdError + 'static)> {
+    error.cause()?.source()
+}
+
+impl<T: 'static + Display + Debug> Inner<T> {
+    /// Return the `Inner<T>` of `error`, if it is an [`Error<T>`](Error) or wraps one
+    #[inline]
+    fn of<'a>(error: &'a (dyn StdError + 'static)) -> Option<&'a Self> {
+        inner_of(error)?.downcast_ref()
+    }
+}
+
+impl<T: 'static + Display + Debug> StdError for Inner<T> {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.layer)
+    }
+}
+
+impl<T: Display> Display for Inner<T> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&self.kind, f)
+    }
+}
+
+impl<T: Debug> Debug for Inner<T> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&self.kind, f)
+    }
+}
+
+impl<T> From<Inner<T>> for Repr<T> {
+    #[inline]
+    // `Inner` is converted to `Box<Inner>` with the `thin` feature
+    #[allow(clippy::useless_conversion)]
+    fn from(inner: Inner<T>) -> Self {
+        Repr(inner.into())
+    }
+}
+
+impl<T> core::ops::Deref for Repr<T> {
+    type Target = Inner<T>;
+
+    #[inline]
+    fn deref(&self) -> &Inner<T> {
+        &self.0
+    }
+}
+
+impl<T> core::ops::DerefMut for Repr<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Inner<T> {
+        &mut self.0
+    }
+}
+
+impl<T: 'static + Display + Debug> StdError for Repr<T> {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&**self)
+    }
+}
+
+impl<T: Display> Display for Repr<T> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<T: Debug> Debug for Repr<T> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl StdError for Layer {}
+
+impl Display for Layer {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.occurrence {
+            Some(ref o) => Display::fmt(o, f),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Debug for Layer {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Layer")
+            .field("occurrence", &self.occurrence)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Layer {
+    /// Return the `Layer` of `error`, if it is an [`Error<T>`](Error) of any `T` or wraps one
+    #[inline]
+    fn of<'a>(error: &'a (dyn StdError + 'static)) -> Option<&'a Layer> {
+        inner_of(error)?.source()?.downcast_ref()
+    }
+
+    #[inline]
+    fn extras_mut(&mut self) -> &mut Extras {
+        self.extras.get_or_insert_with(Default::default)
+    }
+
+    #[inline]
+    fn fields(&self) -> &[Field] {
+        self.extras.as_ref().map_or(&[], |extras| &extras.fields)
+    }
+
+    #[inline]
+    fn hints(&self) -> &[Hint] {
+        self.extras.as_ref().map_or(&[], |extras| &extras.hints)
+    }
+
+    #[inline]
+    fn suppressed(&self) -> &[Cause] {
+        self.extras
+            .as_ref()
+            .map_or(&[], |extras| &extras.suppressed)
+    }
+
+    /// Return the attachment of type `A`, if any
+    fn attachment<A: Any + Send + Sync>(&self) -> Option<&A> {
+        self.extras
+            .as_ref()?
+            .attachments
+            .iter()
+            .find_map(|a| (**a).downcast_ref::<A>())
+    }
+
+    /// Store the attachment `value`, replacing any former attachment of type `A`
+    fn attach<A: Any + Send + Sync>(&mut self, value: A) {
+        let attachments = &mut self.extras_mut().attachments;
+        attachments.retain(|a| !(**a).is::<A>());
+        #[cfg(not(feature = "arc"))]
+        attachments.push(Box::new(value));
+        #[cfg(feature = "arc")]
+        attachments.push(Arc::new(value));
+    }
+}
+
+/// A named value attached to an [`Error`](Error)
+///
+/// The value is stored in its `Debug` representation.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Field {
+    name: &'static str,
+    value: String,
+}
+
+impl Field {
+    /// Create a new `Field` with the `Debug` representation of `value`
+    #[inline]
+    pub fn new<V: Debug + ?Sized>(name: &'static str, value: &V) -> Self {
+        Field {
+            name,
+            value: format!("{:?}", value),
+        }
+    }
+
+    /// Return the name of the field
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Return the `Debug` representation of the value
+    #[inline]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Display for Field {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}={}", self.name, self.value)
+    }
+}
+
+impl Debug for Field {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_map()
+            .entry(&self.name, &format_args!("{}", self.value))
+            .finish()
+    }
+}
+
+/// A help or note message for the user, shown after the error chain in the `{:#}` output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hint {
+    /// Tells the user, how to fix the error
+    Help(String),
+    /// Additional information about the error
+    Note(String),
+}
+
+impl Display for Hint {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Hint::Help(s) => write!(f, "help: {}", s),
+            Hint::Note(s) => write!(f, "note: {}", s),
+        }
+    }
+}
+
+/// `Debug` helper to show a list of [`Field`](Field) as map
+struct Fields<'a>(&'a [Field]);
+
+impl Debug for Fields<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut f = f.debug_map();
+        for field in self.0 {
+            f.entry(&field.name, &format_args!("{}", field.value));
+        }
+        f.finish()
+    }
+}
+
+/// The severity of an error, attached with [`Error::attach()`](Error::attach)
+///
+/// [`Error::severity()`](Error::severity) returns the highest severity of an error chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The operation failed, but the program can carry on as usual
+    Warning,
+    /// The operation failed
+    Error,
+    /// The program can't carry on
+    Fatal,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Fatal => "fatal",
+        })
+    }
+}
+
+/// Whether an operation, which failed with an error, may succeed when retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Retryability {
+    /// The operation may succeed when retried
+    Transient,
+    /// The operation will fail again
+    Permanent,
+}
+
+/// The classification of an error chain returned by [`Error::retryability()`](Error::retryability)
+#[derive(Debug, Clone, Copy)]
+pub struct Verdict<'a> {
+    /// Whether the failed operation may be retried
+    pub retryability: Retryability,
+    /// The error in the chain, which decided the classification
+    pub layer: &'a (dyn StdError + 'static),
+}
+
+/// Classification of error kinds as transient or permanent
+///
+/// Register the kind in [`Classifiers`](Classifiers) to classify every error of the kind with
+/// [`Error::retryability_with()`](Error::retryability_with), or use
+/// [`Error::classified()`](Error::classified) to store the classification in a single error.
+pub trait Classify {
+    /// Return the [`Retryability`](Retryability), or `None` if it can't be decided
+    fn retryability(&self) -> Option<Retryability>;
+}
+
+#[cfg(feature = "std")]
+impl Classify for std::io::ErrorKind {
+    fn retryability(&self) -> Option<Retryability> {
+        use std::io::ErrorKind::*;
+
+        match self {
+            Interrupted | WouldBlock | TimedOut | ConnectionReset | ConnectionAborted => {
+                Some(Retryability::Transient)
+            }
+            NotFound | PermissionDenied | AlreadyExists | InvalidInput | InvalidData
+            | Unsupported => Some(Retryability::Permanent),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Classify for std::io::Error {
+    #[inline]
+    fn retryability(&self) -> Option<Retryability> {
+        self.kind().retryability()
+    }
+}
+
+/// A set of the kinds implementing [`Classify`](Classify)
+///
+/// [`Error::retryability_with()`](Error::retryability_with) classifies every
+/// [`Error<K>`](Error) of a registered kind `K` in the chain, also if it is wrapped in a type
+/// created with [`err_kind!`](err_kind).
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::{Classifiers, Classify, Context as _, Retryability};
+///
+/// #[derive(Debug)]
+/// enum DbError {
+///     Deadlock,
+///     Constraint,
+/// }
+/// # impl std::fmt::Display for DbError {
+/// #     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// #         write!(f, "{:?}", self)
+/// #     }
+/// # }
+///
+/// impl Classify for DbError {
+///     fn retryability(&self) -> Option<Retryability> {
+///         match self {
+///             DbError::Deadlock => Some(Retryability::Transient),
+///             DbError::Constraint => Some(Retryability::Permanent),
+///         }
+///     }
+/// }
+///
+/// let mut classifiers = Classifiers::new();
+/// classifiers.register::<DbError>();
+///
+/// let err = Err::<(), _>("deadlock detected")
+///     .context(DbError::Deadlock)
+///     .context("saving")
+///     .unwrap_err();
+/// let verdict = err.retryability_with(&classifiers).unwrap();
+/// assert_eq!(verdict.retryability, Retryability::Transient);
+/// assert!(err.retryability().is_none());
+/// ```
+#[derive(Default)]
+pub struct Classifiers {
+    kinds: Vec<ClassifiedKind>,
+}
+
+struct ClassifiedKind {
+    type_id: TypeId,
+    retryability_of: fn(&(dyn StdError + 'static)) -> Option<Retryability>,
+}
+
+impl Classifiers {
+    /// Create an empty set
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the kind `K`
+    pub fn register<K: Classify + 'static + Display + Debug>(&mut self) -> &mut Self {
+        let type_id = TypeId::of::<K>();
+
+        if !self.kinds.iter().any(|k| k.type_id == type_id) {
+            self.kinds.push(ClassifiedKind {
+                type_id,
+                retryability_of: |e| Inner::<K>::of(e)?.kind.retryability(),
+            });
+        }
+
+        self
+    }
+}
+
+/// Return the [`Retryability`](Retryability) of a single error of the chain
+fn retryability_of(
+    error: &(dyn StdError + 'static),
+    classifiers: &Classifiers,
+) -> Option<Retryability> {
+    if let Some(layer) = Layer::of(error) {
+        if let Some(retryability) = layer.attachment() {
+            return Some(*retryability);
+        }
+
+        if let Some(retryability) = classifiers
+            .kinds
+            .iter()
+            .find_map(|k| (k.retryability_of)(error))
+        {
+            return Some(retryability);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    {
+        if let Some(e) = error.downcast_ref::<std::io::Error>() {
+            return e.retryability();
+        }
+
+        if let Some(e) = error.downcast_ref::<Error<std::io::ErrorKind>>() {
+            return e.kind().retryability();
+        }
+    }
+
+    None
+}
+
+/// convenience type alias
+pub type Result<O, E> = core::result::Result<O, Error<E>>;
+
+/// The place where an [`Error`](Error) was created
+#[derive(Clone, PartialEq, Eq)]
+pub enum Occurrence {
+    /// The source `Location` recorded via `#[track_caller]`
+    Location(&'static Location<'static>),
+    /// A custom description of the occurrence
+    Custom(String),
+}
+
+impl Occurrence {
+    /// Return the source `Location` of the caller as `Occurrence`
+    #[track_caller]
+    #[inline]
+    pub fn caller() -> Self {
+        Occurrence::Location(Location::caller())
+    }
+
+    /// Return the source file name, if the occurrence is a source `Location`
+    #[inline]
+    pub fn file(&self) -> Option<&str> {
+        match self {
+            Occurrence::Location(l) => Some(l.file()),
+            Occurrence::Custom(_) => None,
+        }
+    }
+
+    /// Return the source line number, if the occurrence is a source `Location`
+    #[inline]
+    pub fn line(&self) -> Option<u32> {
+        match self {
+            Occurrence::Location(l) => Some(l.line()),
+            Occurrence::Custom(_) => None,
+        }
+    }
+
+    /// Return the source column, if the occurrence is a source `Location`
+    #[inline]
+    pub fn column(&self) -> Option<u32> {
+        match self {
+            Occurrence::Location(l) => Some(l.column()),
+            Occurrence::Custom(_) => None,
+        }
+    }
+}
+
+/// Return the [`Occurrence`](Occurrence) of the caller, unless built with
+/// `--cfg chainerror_no_location`
+#[cfg_attr(not(chainerror_no_location), track_caller)]
+#[inline]
+fn caller_occurrence() -> Option<Occurrence> {
+    #[cfg(not(chainerror_no_location))]
+    let occurrence = Some(Occurrence::caller());
+    #[cfg(chainerror_no_location)]
+    let occurrence = None;
+    occurrence
+}
+
+impl Display for Occurrence {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Occurrence::Location(l) => Display::fmt(l, f),
+            Occurrence::Custom(s) => Display::fmt(s, f),
+        }
+    }
+}
+
+impl Debug for Occurrence {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl From<&'static Location<'static>> for Occurrence {
+    #[inline]
+    fn from(l: &'static Location<'static>) -> Self {
+        Occurrence::Location(l)
+    }
+}
+
+impl From<String> for Occurrence {
+    #[inline]
+    fn from(s: String) -> Self {
+        Occurrence::Custom(s)
+    }
+}
+
+impl From<&str> for Occurrence {
+    #[inline]
+    fn from(s: &str) -> Self {
+        Occurrence::Custom(s.into())
+    }
+}
+
+impl<T: 'static + Display + Debug> Error<T> {
+    /// Use the `context()` or `map_context()` Result methods instead of calling this directly
+    #[inline]
+    pub fn new(
+        kind: T,
+        error_cause: Option<Box<dyn StdError + 'static + Send + Sync>>,
+        occurrence: Option<String>,
+    ) -> Self {
+        Self::new_with_occurrence(kind, error_cause, occurrence.map(Occurrence::Custom))
+    }
+
+    /// Same as `new()`, but with a structured [`Occurrence`](Occurrence)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::{Error, Occurrence};
+    ///
+    /// let err = Error::new_with_occurrence("error", None, Some(Occurrence::caller()));
+    /// assert_eq!(err.file(), Some(file!()));
+    /// assert_eq!(err.line(), Some(line!() - 2));
+    /// ```
+    #[inline]
+    pub fn new_with_occurrence(
+        kind: T,
+        error_cause: Option<Box<dyn StdError + 'static + Send + Sync>>,
+        occurrence: Option<Occurrence>,
+    ) -> Self {
+        Self::with_cause(kind, error_cause.map(Cause::new), occurrence)
+    }
+
+    /// Create the error with an already stored error cause
+    #[inline]
+    // `Captured` is converted to `Box<Captured>` or `Arc<Captured>`, depending on the features
+    #[allow(clippy::useless_conversion)]
+    fn with_cause(kind: T, error_cause: Option<Cause>, occurrence: Option<Occurrence>) -> Self {
+        Self {
+            inner: Inner {
+                layer: Layer {
+                    occurrence,
+                    error_cause,
+                    extras: None,
+                    captured: Captured::now().into(),
+                },
+                kind,
+            }
+            .into(),
+        }
+    }
+
+    /// Move the [`Inner<T>`](Inner) out of the error
+    #[inline]
+    fn into_inner(self) -> Inner<T> {
+        #[cfg(not(feature = "thin"))]
+        let inner = self.inner.0;
+        #[cfg(feature = "thin")]
+        let inner = *self.inner.0;
+        inner
+    }
+
+    /// Return the [`Backtrace`](std::backtrace::Backtrace) captured, when the error was created
+    ///
+    /// The backtrace is only captured, if enabled via the `RUST_BACKTRACE` or
+    /// `RUST_LIB_BACKTRACE` environment variables. See [`Backtrace::capture()`].
+    #[cfg(feature = "backtrace")]
+    #[clippy::msrv = "1.65"]
+    #[inline]
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.inner.layer.captured.backtrace
+    }
+
+    /// Return the wall clock time, when the error was created
+    #[cfg(feature = "timestamp")]
+    #[inline]
+    pub fn timestamp(&self) -> SystemTime {
+        self.inner.layer.captured.timestamp
+    }
+
+    /// Return the monotonic time, when the error was created
+    ///
+    /// Use it to measure the time between the errors of a chain.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use chainerror::ErrorDown as _;
+    /// use std::error::Error as _;
+    ///
+    /// let err = Err::<(), _>("timeout")
+    ///     .context("retry 1")
+    ///     .context("retry 2")
+    ///     .unwrap_err();
+    ///
+    /// let inner = err.source().unwrap().downcast_chain_ref::<&str>().unwrap();
+    /// let elapsed = err.instant().duration_since(inner.instant());
+    /// # let _ = elapsed;
+    /// ```
+    #[cfg(feature = "timestamp")]
+    #[inline]
+    pub fn instant(&self) -> Instant {
+        self.inner.layer.captured.instant
+    }
+
+    /// Return the id of the thread, which created the error
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use std::thread;
+    ///
+    /// let err = thread::Builder::new()
+    ///     .name("worker".into())
+    ///     .spawn(|| Err::<(), _>("failed").context("in worker"))
+    ///     .unwrap()
+    ///     .join()
+    ///     .unwrap()
+    ///     .unwrap_err();
+    ///
+    /// assert_ne!(err.thread_id(), thread::current().id());
+    /// assert_eq!(err.thread_name(), Some("worker"));
+    /// ```
+    #[cfg(feature = "thread")]
+    #[inline]
+    pub fn thread_id(&self) -> ThreadId {
+        self.inner.layer.captured.thread.id()
+    }
+
+    /// Return the name of the thread, which created the error, if it has one
+    #[cfg(feature = "thread")]
+    #[inline]
+    pub fn thread_name(&self) -> Option<&str> {
+        self.inner.layer.captured.thread.name()
+    }
+
+    /// Return the [`SpanTrace`](tracing_error::SpanTrace) of the `tracing` spans entered,
+    /// when the error was created
+    ///
+    /// The span trace is only captured, if the current subscriber has a
+    /// [`tracing_error::ErrorLayer`].
+    #[cfg(feature = "tracing")]
+    #[inline]
+    pub fn span_trace(&self) -> &SpanTrace {
+        &self.inner.layer.captured.span_trace
+    }
+
+    /// Emit one `tracing` event at `level` for every error in the chain
+    ///
+    /// The events are emitted inside an `error_chain` span with the field `error`, the `Display`
+    /// of this error. Every event has the structured fields `index` (`0` for this error),
+    /// `error` (the `Display` of the error) and, if the error is an [`Error`](Error) or wraps
+    /// one, `kind` (the `Debug` of its kind) and `occurrence`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    ///
+    /// let err = Err::<(), _>("connection refused")
+    ///     .context("connecting to the database")
+    ///     .unwrap_err();
+    ///
+    /// err.trace(tracing::Level::WARN);
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn trace(&self, level: tracing::Level) {
+        use tracing::Level;
+
+        macro_rules! trace {
+            ($level:expr) => {
+                tracing::span!($level, "error_chain", error = %self).in_scope(|| {
+                    for (index, e) in self.iter().enumerate() {
+                        let occurrence = Layer::of(e).and_then(|l| l.occurrence.as_ref());
+                        tracing::event!(
+                            $level,
+                            index,
+                            error = %e,
+                            kind = inner_of(e).map(tracing::field::debug),
+                            occurrence = occurrence.map(tracing::field::display),
+                        );
+                    }
+                })
+            };
+        }
+
+        match level {
+            Level::TRACE => trace!(Level::TRACE),
+            Level::DEBUG => trace!(Level::DEBUG),
+            Level::INFO => trace!(Level::INFO),
+            Level::WARN => trace!(Level::WARN),
+            Level::ERROR => trace!(Level::ERROR),
+        }
+    }
+
+    /// Attach a named `value` to the error, which is stored in its `Debug` representation
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use std::path::PathBuf;
+    ///
+    /// let path = PathBuf::from("_non_existent.txt");
+    /// let err = std::fs::read_to_string(&path)
+    ///     .context("reading config")
+    ///     .map_err(|e| e.field("path", &path).field("uid", &1000))
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(err.fields()[0].name(), "path");
+    /// assert_eq!(err.fields()[0].value(), "\"_non_existent.txt\"");
+    /// assert_eq!(err.fields()[1].to_string(), "uid=1000");
+    /// ```
+    #[inline]
+    pub fn field<V: Debug + ?Sized>(mut self, name: &'static str, value: &V) -> Self {
+        self.inner
+            .layer
+            .extras_mut()
+            .fields
+            .push(Field::new(name, value));
+        self
+    }
+
+    /// Return the fields attached to this error, not including the fields of the error causes
+    ///
+    /// Use [`ErrorDown::fields()`](ErrorDown::fields) on the items of [`iter()`](Error::iter)
+    /// to get the fields of the whole error chain.
+    #[inline]
+    pub fn fields(&self) -> &[Field] {
+        self.inner.layer.fields()
+    }
+
+    /// Attach a typed `value` to the error, replacing a former attachment of the same type
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use std::io;
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// struct RetryAfter(Duration);
+    ///
+    /// fn fetch() -> chainerror::Result<(), &'static str> {
+    ///     Err(io::Error::from(io::ErrorKind::TimedOut))
+    ///         .context("fetching")
+    ///         .map_err(|e| e.attach(RetryAfter(Duration::from_secs(5))))
+    /// }
+    ///
+    /// fn update() -> chainerror::Result<(), &'static str> {
+    ///     fetch().context("updating")
+    /// }
+    ///
+    /// let err = update().unwrap_err();
+    /// assert!(err.attachment::<RetryAfter>().is_none());
+    /// assert_eq!(
+    ///     err.request::<RetryAfter>(),
+    ///     Some(&RetryAfter(Duration::from_secs(5)))
+    /// );
+    /// ```
+    #[inline]
+    pub fn attach<A: Any + Send + Sync>(mut self, value: A) -> Self {
+        self.inner.layer.attach(value);
+        self
+    }
+
+    /// Return the attachment of type `A` of this error, not looking at the error causes
+    #[inline]
+    pub fn attachment<A: Any + Send + Sync>(&self) -> Option<&A> {
+        self.inner.layer.attachment()
+    }
+
+    /// Find the first attachment of type `A` in the error chain, starting with this error
+    ///
+    /// Every child of a [`MultiError`](MultiError) in the chain is searched.
+    #[inline]
+    pub fn request<A: Any + Send + Sync>(&self) -> Option<&A> {
+        self.request_all().next()
+    }
+
+    /// Returns an Iterator over all attachments of type `A` in the error chain,
+    /// starting with this error
+    ///
+    /// Every child of a [`MultiError`](MultiError) in the chain is searched.
+    #[inline]
+    pub fn request_all<A: Any + Send + Sync>(&self) -> impl Iterator<Item = &A> {
+        tree(self)
+            .filter_map(Layer::of)
+            .filter_map(Layer::attachment)
+    }
+
+    /// Add a help message to the error, which tells the user how to fix it
+    ///
+    /// The `{:#}` output shows the help and note messages of the whole error chain after the
+    /// chain, in the order the errors were created.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    ///
+    /// let err = std::fs::read_to_string("_non_existent.toml")
+    ///     .context("Reading file: _non_existent.toml")
+    ///     .map_err(|e| e.help("run `tool init` to create a config"))
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(
+    ///     format!("{:#}", err).lines().last(),
+    ///     Some("help: run `tool init` to create a config")
+    /// );
+    /// ```
+    #[inline]
+    pub fn help<S: Into<String>>(mut self, help: S) -> Self {
+        self.inner
+            .layer
+            .extras_mut()
+            .hints
+            .push(Hint::Help(help.into()));
+        self
+    }
+
+    /// Add a note with additional information to the error
+    #[inline]
+    pub fn note<S: Into<String>>(mut self, note: S) -> Self {
+        self.inner
+            .layer
+            .extras_mut()
+            .hints
+            .push(Hint::Note(note.into()));
+        self
+    }
+
+    /// Return the help and note messages of this error, not including those of the error causes
+    #[inline]
+    pub fn hints(&self) -> &[Hint] {
+        self.inner.layer.hints()
+    }
+
+    /// Add a secondary `error`, which occurred while handling this error, e.g. in a cleanup
+    ///
+    /// Suppressed errors don't change the `source()` chain. They are shown in the `{:#?}` output
+    /// and returned by [`suppressed()`](Error::suppressed).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use std::io;
+    ///
+    /// fn write() -> Result<(), io::Error> {
+    ///     Err(io::Error::from(io::ErrorKind::WriteZero))
+    /// }
+    ///
+    /// fn unlink() -> Result<(), io::Error> {
+    ///     Err(io::Error::from(io::ErrorKind::PermissionDenied))
+    /// }
+    ///
+    /// fn save() -> chainerror::Result<(), &'static str> {
+    ///     write().context("writing the temporary file").map_err(|e| match unlink() {
+    ///         Ok(()) => e,
+    ///         Err(cleanup) => e.suppress(cleanup),
+    ///     })
+    /// }
+    ///
+    /// let err = save().unwrap_err();
+    /// assert_eq!(err.suppressed().count(), 1);
+    /// assert_eq!(
+    ///     err.find_cause::<io::Error>().unwrap().kind(),
+    ///     io::ErrorKind::WriteZero
+    /// );
+    /// assert!(format!("{:#?}", err).contains("suppressed: ["));
+    /// ```
+    #[inline]
+    pub fn suppress<E: Into<Box<dyn StdError + 'static + Send + Sync>>>(
+        mut self,
+        error: E,
+    ) -> Self {
+        self.inner
+            .layer
+            .extras_mut()
+            .suppressed
+            .push(Cause::new(error.into()));
+        self
+    }
+
+    /// Returns an Iterator over the suppressed errors of the whole error chain,
+    /// starting with those of this error
+    ///
+    /// Every child of a [`MultiError`](MultiError) in the chain is searched.
+    #[inline]
+    pub fn suppressed(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
+        tree(self)
+            .filter_map(Layer::of)
+            .flat_map(Layer::suppressed)
+            .map(|e| &**e)
+    }
+
+    /// Return the highest [`Severity`](Severity) attached to any error in the chain
+    ///
+    /// Every child of a [`MultiError`](MultiError) in the chain is searched.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use chainerror::Decorate as _;
+    /// use chainerror::Severity;
+    ///
+    /// let err = Err::<(), _>("disk full")
+    ///     .context("writing the journal")
+    ///     .attach(Severity::Fatal)
+    ///     .context("saving")
+    ///     .attach(Severity::Warning)
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(err.severity(), Some(Severity::Fatal));
+    /// assert_eq!(format!("{:#}", err).lines().next(), Some("warning: saving"));
+    /// ```
+    #[inline]
+    pub fn severity(&self) -> Option<Severity> {
+        self.request_all::<Severity>().max().copied()
+    }
+
+    /// Classify the error chain as transient or permanent
+    ///
+    /// Returns the [`Verdict`](Verdict) of the first error in the chain, which can be
+    /// classified, see [`ErrorDown::retryability()`](ErrorDown::retryability).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// use chainerror::Context as _;
+    /// use chainerror::Retryability;
+    /// use std::io;
+    ///
+    /// fn fetch() -> chainerror::Result<(), &'static str> {
+    ///     Err(io::Error::from(io::ErrorKind::ConnectionReset)).context("fetching")
+    /// }
+    ///
+    /// let err = fetch().context("updating").unwrap_err();
+    /// let verdict = err.retryability().unwrap();
+    ///
+    /// assert_eq!(verdict.retryability, Retryability::Transient);
+    /// assert!(verdict.layer.is::<io::Error>());
+    /// assert!(err.is_transient());
+    /// # }
+    /// ```
+    #[inline]
+    pub fn retryability(&self) -> Option<Verdict<'_>> {
+        self.retryability_with(&Classifiers::new())
+    }
+
+    /// Classify the error chain as transient or permanent, also with the kinds registered in
+    /// `classifiers`
+    ///
+    /// See [`Classifiers`](Classifiers) for an example.
+    #[inline]
+    pub fn retryability_with(&self, classifiers: &Classifiers) -> Option<Verdict<'_>> {
+        self.iter().find_map(|layer| {
+            retryability_of(layer, classifiers).map(|retryability| Verdict {
+                retryability,
+                layer,
+            })
+        })
+    }
+
+    /// Return `true`, if the error chain is classified as [`Transient`](Retryability::Transient)
+    #[inline]
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.retryability(),
+            Some(Verdict {
+                retryability: Retryability::Transient,
+                ..
+            })
+        )
+    }
+
+    /// Return the [`Occurrence`](Occurrence) of the error, if any was recorded
+    #[inline]
+    pub fn occurrence(&self) -> Option<&Occurrence> {
+        self.inner.layer.occurrence.as_ref()
+    }
+
+    /// Return the source file name, where the error was created
+    #[inline]
+    pub fn file(&self) -> Option<&str> {
+        self.inner
+            .layer
+            .occurrence
+            .as_ref()
+            .and_then(Occurrence::file)
+    }
+
+    /// Return the source line number, where the error was created
+    #[inline]
+    pub fn line(&self) -> Option<u32> {
+        self.inner
+            .layer
+            .occurrence
+            .as_ref()
+            .and_then(Occurrence::line)
+    }
+
+    /// Return the source column, where the error was created
+    #[inline]
+    pub fn column(&self) -> Option<u32> {
+        self.inner
+            .layer
+            .occurrence
+            .as_ref()
+            .and_then(Occurrence::column)
+    }
+
+    /// return the root cause of the error chain, if any exists
+    pub fn root_cause(&self) -> Option<&(dyn StdError + 'static)> {
+        self.iter().last()
+    }
+
+    /// Find the first error cause of type U, if any exists
+    ///
+    /// All children of a [`MultiError`](MultiError) in the chain are searched depth-first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use chainerror::ErrorDown as _;
+    /// use std::error::Error;
+    /// use std::io;
+    ///
+    /// fn do_some_io() -> Result<(), Box<dyn Error + Send + Sync>> {
+    ///     Err(io::Error::from(io::ErrorKind::NotFound))?;
+    ///     Ok(())
+    /// }
+    ///
+    /// chainerror::str_context!(Func2Error);
+    ///
+    /// fn func2() -> Result<(), Box<dyn Error + Send + Sync>> {
+    ///     let filename = "foo.txt";
+    ///     do_some_io().context(Func2Error(format!("Error reading '{}'", filename)))?;
+    ///     Ok(())
+    /// }
+    ///
+    /// chainerror::str_context!(Func1Error);
+    ///
+    /// fn func1() -> Result<(), Box<dyn Error + Send + Sync>> {
+    ///     func2().context(Func1Error::new("func1 error"))?;
+    ///     Ok(())
+    /// }
+    ///
+    /// if let Err(e) = func1() {
+    ///     if let Some(f1err) = e.downcast_chain_ref::<Func1Error>() {
+    ///         assert!(f1err.find_cause::<io::Error>().is_some());
+    ///
+    ///         assert!(f1err.find_chain_cause::<Func2Error>().is_some());
+    ///     }
+    /// #        else {
+    /// #            panic!();
+    /// #        }
+    /// }
+    /// #    else {
+    /// #         unreachable!();
+    /// #    }
+    /// ```
+    #[inline]
+    pub fn find_cause<U: StdError + 'static>(&self) -> Option<&U> {
+        tree(self)
+            .filter_map(<dyn StdError>::downcast_ref::<U>)
+            .next()
+    }
+
+    /// Find the first error cause of type [`Error<U>`](Error), if any exists
+    ///
+    /// Same as `find_cause`, but hides the [`Error<U>`](Error) implementation internals
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # chainerror::str_context!(FooError);
+    /// # let err = chainerror::Error::new(String::new(), None, None);
+    /// // Instead of writing
+    /// err.find_cause::<chainerror::Error<FooError>>();
+    ///
+    /// // leave out the chainerror::Error<FooError> implementation detail
+    /// err.find_chain_cause::<FooError>();
+    /// ```
+    #[inline]
+    pub fn find_chain_cause<U: StdError + 'static>(&self) -> Option<&Error<U>> {
+        tree(self)
+            .filter_map(<dyn StdError>::downcast_ref::<Error<U>>)
+            .next()
+    }
+
+    /// Find the first error cause of type [`Error<U>`](Error) or `U`, if any exists and return `U`
+    ///
+    /// Same as `find_cause` and `find_chain_cause`, but hides the [`Error<U>`](Error) implementation internals
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # chainerror::str_context!(FooErrorKind);
+    /// # let err = chainerror::Error::new(String::new(), None, None);
+    /// // Instead of writing
+    /// err.find_cause::<chainerror::Error<FooErrorKind>>();
+    /// // and/or
+    /// err.find_chain_cause::<FooErrorKind>();
+    /// // and/or
+    /// err.find_cause::<FooErrorKind>();
+    ///
+    /// // leave out the chainerror::Error<FooErrorKind> implementation detail
+    /// err.find_kind_or_cause::<FooErrorKind>();
+    /// ```
+    #[inline]
+    pub fn find_kind_or_cause<U: StdError + 'static>(&self) -> Option<&U> {
+        tree(self)
+            .filter_map(|e| {
+                e.downcast_ref::<Error<U>>()
+                    .map(|e| e.kind())
+                    .or_else(|| e.downcast_ref::<U>())
+            })
+            .next()
+    }
+
+    /// Return a reference to T of [`Error<T>`](Error)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use std::error::Error;
+    /// use std::io;
+    ///
+    /// fn do_some_io() -> Result<(), Box<dyn Error + Send + Sync>> {
+    ///     Err(io::Error::from(io::ErrorKind::NotFound))?;
+    ///     Ok(())
+    /// }
+    ///
+    /// chainerror::str_context!(Func2Error);
+    ///
+    /// fn func2() -> Result<(), Box<dyn Error + Send + Sync>> {
+    ///     let filename = "foo.txt";
+    ///     do_some_io().context(Func2Error(format!("Error reading '{}'", filename)))?;
+    ///     Ok(())
+    /// }
+    ///
+    /// #[derive(Debug)]
+    /// enum Func1ErrorKind {
+    ///     Func2,
+    ///     IO(String),
+    /// }
+    ///
+    /// /// impl ::std::fmt::Display for Func1ErrorKind {…}
+    /// # impl ::std::fmt::Display for Func1ErrorKind {
+    /// #     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    /// #         match self {
+    /// #             Func1ErrorKind::Func2 => write!(f, "func1 error calling func2"),
+    /// #             Func1ErrorKind::IO(filename) => write!(f, "Error reading '{}'", filename),
+    /// #         }
+    /// #     }
+    /// # }
+    ///
+    /// fn func1() -> chainerror::Result<(), Func1ErrorKind> {
+    ///     func2().context(Func1ErrorKind::Func2)?;
+    ///     do_some_io().context(Func1ErrorKind::IO("bar.txt".into()))?;
+    ///     Ok(())
+    /// }
+    ///
+    /// if let Err(e) = func1() {
+    ///     match e.kind() {
+    ///         Func1ErrorKind::Func2 => {}
+    ///         Func1ErrorKind::IO(filename) => panic!(),
+    ///     }
+    /// }
+    /// #    else {
+    /// #         unreachable!();
+    /// #    }
+    /// ```
+    #[inline]
+    pub fn kind(&self) -> &T {
+        &self.inner.kind
+    }
+
+    /// Return the kind `T` of [`Error<T>`](Error), dropping the error cause
+    #[inline]
+    pub fn into_kind(self) -> T {
+        self.into_inner().kind
+    }
+
+    /// Return the error cause of [`Error<T>`](Error), if any exists, dropping the kind
+    #[inline]
+    pub fn into_source(self) -> Option<Cause> {
+        self.into_inner().layer.error_cause
+    }
+
+    /// Decompose [`Error<T>`](Error) into its kind, error cause and occurrence
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use std::io;
+    ///
+    /// let err = Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+    ///     .context("reading")
+    ///     .unwrap_err();
+    ///
+    /// let (kind, source, occurrence) = err.into_parts();
+    /// assert_eq!(kind, "reading");
+    /// assert!(source.unwrap().is::<io::Error>());
+    ///
+    /// // `None`, if built with `--cfg chainerror_no_location`
+    /// if let Some(occurrence) = occurrence {
+    ///     assert_eq!(occurrence.file(), Some(file!()));
+    /// }
+    /// ```
+    #[inline]
+    pub fn into_parts(self) -> (T, Option<Cause>, Option<Occurrence>) {
+        let Inner { layer, kind } = self.into_inner();
+        (kind, layer.error_cause, layer.occurrence)
+    }
+
+    /// Replace the error cause of [`Error<T>`](Error), keeping the kind and the occurrence
+    #[inline]
+    pub fn with_source<E: Into<Box<dyn StdError + 'static + Send + Sync>>>(
+        mut self,
+        error_cause: E,
+    ) -> Self {
+        self.inner.layer.error_cause = Some(Cause::from_error(error_cause));
+        self
+    }
+
+    /// Map [`Error<T>`](Error) to [`Error<U>`](Error) by applying a function to the kind,
+    /// keeping the occurrence and the error cause
+    ///
+    /// Unlike `context()`, no extra layer is added to the error chain.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use std::io;
+    ///
+    /// #[derive(Debug)]
+    /// enum ParseError {
+    ///     Syntax(String),
+    /// }
+    /// # impl std::fmt::Display for ParseError {
+    /// #     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    /// #         write!(f, "{:?}", self)
+    /// #     }
+    /// # }
+    ///
+    /// #[derive(Debug)]
+    /// enum ConfigError {
+    ///     Parse(ParseError),
+    /// }
+    /// # impl std::fmt::Display for ConfigError {
+    /// #     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    /// #         write!(f, "{:?}", self)
+    /// #     }
+    /// # }
+    ///
+    /// fn parse() -> chainerror::Result<(), ParseError> {
+    ///     Err(io::Error::from(io::ErrorKind::InvalidData))
+    ///         .context(ParseError::Syntax("line 1".into()))
+    /// }
+    ///
+    /// fn read_config() -> chainerror::Result<(), ConfigError> {
+    ///     parse().map_err(|e| e.map_kind(ConfigError::Parse))
+    /// }
+    ///
+    /// let err = read_config().unwrap_err();
+    /// assert!(matches!(err.kind(), ConfigError::Parse(ParseError::Syntax(_))));
+    /// assert_eq!(err.iter().count(), 2);
+    /// ```
+    #[inline]
+    // `Inner` is converted to `Box<Inner>` with the `thin` feature
+    #[allow(clippy::useless_conversion)]
+    pub fn map_kind<U: 'static + Display + Debug, F: FnOnce(T) -> U>(self, op: F) -> Error<U> {
+        let Inner { layer, kind } = self.into_inner();
+        Error {
+            inner: Inner {
+                layer,
+                kind: op(kind),
+            }
+            .into(),
+        }
+    }
+
+    /// Returns an Iterator over all error causes/sources
+    ///
+    /// # Example
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
+        ErrorIter {
+            current: Some(self),
+        }
+    }
+}
+
+impl<T: 'static + Display + Debug + Classify> Error<T> {
+    /// Attach the [`Retryability`](Retryability) of the kind, so it is found by
+    /// [`retryability()`](Error::retryability) of the whole error chain
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::{Classify, Context as _, Retryability};
+    ///
+    /// #[derive(Debug)]
+    /// enum DbError {
+    ///     Deadlock,
+    ///     Constraint,
+    /// }
+    /// # impl std::fmt::Display for DbError {
+    /// #     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    /// #         write!(f, "{:?}", self)
+    /// #     }
+    /// # }
+    ///
+    /// impl Classify for DbError {
+    ///     fn retryability(&self) -> Option<Retryability> {
+    ///         match self {
+    ///             DbError::Deadlock => Some(Retryability::Transient),
+    ///             DbError::Constraint => Some(Retryability::Permanent),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn insert() -> chainerror::Result<(), DbError> {
+    ///     Err(chainerror::Error::from(DbError::Deadlock).classified())
+    /// }
+    ///
+    /// let err = insert().context("saving").unwrap_err();
+    /// assert!(err.is_transient());
+    /// ```
+    #[inline]
+    pub fn classified(self) -> Self {
+        match self.inner.kind.retryability() {
+            Some(retryability) => self.attach(retryability),
+            None => self,
+        }
+    }
+}
+
+/// Convenience methods for `Result<>` to turn the error into a decorated [`Error`](Error)
+pub trait Context<O, E: Into<Box<dyn StdError + 'static + Send + Sync>>> {
+    /// Decorate the error with a `kind` of type `T` and the source `Location`
+    fn context<T: 'static + Display + Debug>(self, kind: T) -> core::result::Result<O, Error<T>>;
+
+    /// Decorate the error with a `kind` of type `T` produced with `op` and the source `Location`
+    ///
+    /// Unlike `context()`, the `kind` is only created, if there is an error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    ///
+    /// fn read(name: &str) -> chainerror::Result<String, String> {
+    ///     std::fs::read_to_string(name).with_context(|| format!("Reading file: {}", name))
+    /// }
+    ///
+    /// assert_eq!(
+    ///     read("_non_existent.txt").unwrap_err().kind(),
+    ///     "Reading file: _non_existent.txt"
+    /// );
+    /// ```
+    fn with_context<T: 'static + Display + Debug, F: FnOnce() -> T>(
+        self,
+        op: F,
+    ) -> core::result::Result<O, Error<T>>;
+
+    /// Decorate the error just with the source `Location`
+    fn annotate(self) -> core::result::Result<O, Error<AnnotatedError>>;
+
+    /// Decorate the `error` with a `kind` of type `T` produced with a `FnOnce(&error)` and the source `Location`
+    fn map_context<T: 'static + Display + Debug, F: FnOnce(&E) -> T>(
+        self,
+        op: F,
+    ) -> core::result::Result<O, Error<T>>;
+
+    /// Decorate the error with a `kind` of type `T`, named `fields` and the source `Location`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use chainerror::ErrorDown as _;
+    /// use std::path::PathBuf;
+    ///
+    /// fn read_config(path: &PathBuf, uid: u32) -> chainerror::Result<String, &'static str> {
+    ///     std::fs::read_to_string(path)
+    ///         .context_with_fields("reading config", &[("path", &path), ("uid", &uid)])
+    /// }
+    ///
+    /// let err = read_config(&"_non_existent.txt".into(), 1000).unwrap_err();
+    ///
+    /// for e in err.iter() {
+    ///     for field in e.fields() {
+    ///         eprintln!("{}: {}", field.name(), field.value());
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(err.iter().flat_map(|e| e.fields()).count(), 2);
+    /// ```
+    fn context_with_fields<T: 'static + Display + Debug>(
+        self,
+        kind: T,
+        fields: &[(&'static str, &dyn Debug)],
+    ) -> core::result::Result<O, Error<T>>;
+}
+
+/// Convenience type to just decorate the error with the source `Location`
+pub struct AnnotatedError(());
+
+impl Display for AnnotatedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "(passed error)")
+    }
+}
+
+impl Debug for AnnotatedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "(passed error)")
+    }
+}
+
+impl<O, E: Into<Box<dyn StdError + 'static + Send + Sync>>> Context<O, E>
+    for core::result::Result<O, E>
+{
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn context<T: 'static + Display + Debug>(self, kind: T) -> core::result::Result<O, Error<T>> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error_cause) => Err(Error::with_cause(
+                kind,
+                Some(Cause::from_error(error_cause)),
+                caller_occurrence(),
+            )),
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn with_context<T: 'static + Display + Debug, F: FnOnce() -> T>(
+        self,
+        op: F,
+    ) -> core::result::Result<O, Error<T>> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error_cause) => Err(Error::with_cause(
+                op(),
+                Some(Cause::from_error(error_cause)),
+                caller_occurrence(),
+            )),
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn annotate(self) -> core::result::Result<O, Error<AnnotatedError>> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error_cause) => Err(Error::with_cause(
+                AnnotatedError(()),
+                Some(Cause::from_error(error_cause)),
+                caller_occurrence(),
+            )),
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn map_context<T: 'static + Display + Debug, F: FnOnce(&E) -> T>(
+        self,
+        op: F,
+    ) -> core::result::Result<O, Error<T>> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error_cause) => {
+                let kind = op(&error_cause);
+                Err(Error::with_cause(
+                    kind,
+                    Some(Cause::from_error(error_cause)),
+                    caller_occurrence(),
+                ))
+            }
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn context_with_fields<T: 'static + Display + Debug>(
+        self,
+        kind: T,
+        fields: &[(&'static str, &dyn Debug)],
+    ) -> core::result::Result<O, Error<T>> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error_cause) => {
+                let error = Error::with_cause(
+                    kind,
+                    Some(Cause::from_error(error_cause)),
+                    caller_occurrence(),
+                );
+                Err(fields
+                    .iter()
+                    .fold(error, |error, (name, value)| error.field(name, value)))
+            }
+        }
+    }
+}
+
+/// Convenience methods for `Result<O, Error<T>>` to decorate the existing [`Error`](Error)
+/// without an extra layer
+///
+/// To decorate any other error, turn it into an [`Error`](Error) first with
+/// [`Context::context()`](Context::context) or [`Context::annotate()`](Context::annotate).
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::Context as _;
+/// use chainerror::Decorate as _;
+///
+/// let err = std::fs::read_to_string("_non_existent.toml")
+///     .context("Reading file: _non_existent.toml")
+///     .help("run `tool init` to create a config")
+///     .unwrap_err();
+///
+/// assert_eq!(err.iter().count(), 2);
+/// assert_eq!(
+///     format!("{:#}", err).lines().next(),
+///     Some("Reading file: _non_existent.toml")
+/// );
+/// assert_eq!(
+///     format!("{:#}", err).lines().last(),
+///     Some("help: run `tool init` to create a config")
+/// );
+/// ```
+pub trait Decorate<O, T> {
+    /// Add a named field to the error, see [`Error::field()`](Error::field)
+    fn field<V: Debug + ?Sized>(self, name: &'static str, value: &V) -> Self;
+
+    /// Attach a typed `value` to the error, see [`Error::attach()`](Error::attach)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::Context as _;
+    /// use chainerror::Decorate as _;
+    /// use std::io;
+    ///
+    /// struct RequestId(u64);
+    ///
+    /// fn handle() -> chainerror::Result<(), &'static str> {
+    ///     Err(io::Error::from(io::ErrorKind::NotFound))
+    ///         .context("handling request")
+    ///         .attach(RequestId(42))
+    /// }
+    ///
+    /// let err = handle().unwrap_err();
+    /// assert_eq!(err.attachment::<RequestId>().unwrap().0, 42);
+    /// ```
+    fn attach<A: Any + Send + Sync>(self, value: A) -> Self;
+
+    /// Add a help message to the error, see [`Error::help()`](Error::help)
+    fn help<S: Into<String>>(self, help: S) -> Self;
+
+    /// Add a note to the error, see [`Error::note()`](Error::note)
+    fn note<S: Into<String>>(self, note: S) -> Self;
+}
+
+impl<O, T: 'static + Display + Debug> Decorate<O, T> for core::result::Result<O, Error<T>> {
+    #[inline]
+    fn field<V: Debug + ?Sized>(self, name: &'static str, value: &V) -> Self {
+        self.map_err(|e| e.field(name, value))
+    }
+
+    #[inline]
+    fn attach<A: Any + Send + Sync>(self, value: A) -> Self {
+        self.map_err(|e| e.attach(value))
+    }
+
+    #[inline]
+    fn help<S: Into<String>>(self, help: S) -> Self {
+        self.map_err(|e| e.help(help))
+    }
+
+    #[inline]
+    fn note<S: Into<String>>(self, note: S) -> Self {
+        self.map_err(|e| e.note(note))
+    }
+}
+
+/// Convenience methods for `Option<>` to turn `None` into an [`Error`](Error) without a cause
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::Context as _;
+/// use chainerror::ErrorDown as _;
+/// use chainerror::OptionContext as _;
+/// use std::collections::HashMap;
+/// use std::error::Error as _;
+///
+/// fn port(config: &HashMap<&str, u16>) -> chainerror::Result<u16, &'static str> {
+///     config.get("port").copied().context("no port configured")
+/// }
+///
+/// let err = port(&HashMap::new())
+///     .context("starting the server")
+///     .unwrap_err();
+///
+/// let cause = err.source().unwrap().downcast_chain_ref::<&str>().unwrap();
+/// assert_eq!(*cause.kind(), "no port configured");
+/// assert!(cause.source().is_none());
+/// ```
+pub trait OptionContext<O> {
+    /// Turn `None` into an error with `kind` of type `T` and the source `Location`
+    fn context<T: 'static + Display + Debug>(self, kind: T) -> core::result::Result<O, Error<T>>;
+
+    /// Turn `None` into an error with a `kind` of type `T` produced with `op` and the source `Location`
+    fn with_context<T: 'static + Display + Debug, F: FnOnce() -> T>(
+        self,
+        op: F,
+    ) -> core::result::Result<O, Error<T>>;
+
+    /// Turn `None` into an error with just the source `Location`
+    fn annotate(self) -> core::result::Result<O, Error<AnnotatedError>>;
+}
+
+impl<O> OptionContext<O> for Option<O> {
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn context<T: 'static + Display + Debug>(self, kind: T) -> core::result::Result<O, Error<T>> {
+        match self {
+            Some(t) => Ok(t),
+            None => Err(Error::new_with_occurrence(kind, None, caller_occurrence())),
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn with_context<T: 'static + Display + Debug, F: FnOnce() -> T>(
+        self,
+        op: F,
+    ) -> core::result::Result<O, Error<T>> {
+        match self {
+            Some(t) => Ok(t),
+            None => Err(Error::new_with_occurrence(op(), None, caller_occurrence())),
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn annotate(self) -> core::result::Result<O, Error<AnnotatedError>> {
+        match self {
+            Some(t) => Ok(t),
+            None => Err(Error::new_with_occurrence(
+                AnnotatedError(()),
+                None,
+                caller_occurrence(),
+            )),
+        }
+    }
+}
+
+/// Convenience methods for an `Iterator` over `Result<>` items to turn every error into a
+/// decorated [`Error`](Error)
+///
+/// The source `Location` is recorded, where the iterator is built.
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::IteratorContext as _;
+///
+/// let lines = ["1", "2", "x", "4"];
+/// let numbers = lines
+///     .iter()
+///     .map(|line| line.parse::<u32>())
+///     .with_item_context(|index| format!("parsing line #{}", index + 1));
+///
+/// let errors = numbers.filter_map(Result::err).collect::<Vec<_>>();
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].kind(), "parsing line #3");
+/// ```
+pub trait IteratorContext<O, E: Into<Box<dyn StdError + 'static + Send + Sync>>>:
+    Iterator<Item = core::result::Result<O, E>> + Sized
+{
+    /// Decorate every error with a `kind` of type `T` produced with `op` from the index of the
+    /// item and the source `Location`
+    ///
+    /// The index counts all items, not only the errors.
+    fn with_item_context<T: 'static + Display + Debug, F: FnMut(usize) -> T>(
+        self,
+        op: F,
+    ) -> ContextIter<Self, F>;
+
+    /// Collect all items into `C` or return the first error decorated with a `kind` of type `T`
+    /// produced with `op` from the index of the failing item
+    ///
+    /// To record a key instead of the index, look it up in `op`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::IteratorContext as _;
+    /// use std::io;
+    ///
+    /// fn read(file: &str) -> Result<String, io::Error> {
+    ///     Err(io::Error::from(io::ErrorKind::NotFound))
+    /// }
+    ///
+    /// let files = ["foo.txt", "bar.txt"];
+    /// let err = files
+    ///     .iter()
+    ///     .map(|file| read(file))
+    ///     .collect_context::<Vec<_>, _, _>(|index| format!("reading {}", files[index]))
+    ///     .unwrap_err();
+    /// assert_eq!(err.kind(), "reading foo.txt");
+    /// ```
+    fn collect_context<C: FromIterator<O>, T: 'static + Display + Debug, F: FnMut(usize) -> T>(
+        self,
+        op: F,
+    ) -> core::result::Result<C, Error<T>>;
+
+    /// Collect all successful items into `C` and every error decorated with a `kind` of type `T`
+    /// produced with `op` from the index of the failing item
+    ///
+    /// Unlike `collect_context()`, the iterator is always fully consumed, so every failure is
+    /// reported with its own error chain next to the partial success.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chainerror::IteratorContext as _;
+    ///
+    /// let (numbers, errors) = ["1", "x", "3", "y"]
+    ///     .iter()
+    ///     .map(|s| s.parse::<u32>())
+    ///     .partition_context::<Vec<_>, _, _>(|index| format!("parsing item #{}", index));
+    ///
+    /// assert_eq!(numbers, [1, 3]);
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!(errors[1].kind(), "parsing item #3");
+    /// ```
+    fn partition_context<
+        C: Default + Extend<O>,
+        T: 'static + Display + Debug,
+        F: FnMut(usize) -> T,
+    >(
+        self,
+        op: F,
+    ) -> (C, Vec<Error<T>>);
+}
+
+impl<O, E, I> IteratorContext<O, E> for I
+where
+    E: Into<Box<dyn StdError + 'static + Send + Sync>>,
+    I: Iterator<Item = core::result::Result<O, E>>,
+{
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn with_item_context<T: 'static + Display + Debug, F: FnMut(usize) -> T>(
+        self,
+        op: F,
+    ) -> ContextIter<Self, F> {
+        ContextIter {
+            iter: self,
+            op,
+            index: 0,
+            occurrence: caller_occurrence(),
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn collect_context<C: FromIterator<O>, T: 'static + Display + Debug, F: FnMut(usize) -> T>(
+        self,
+        op: F,
+    ) -> core::result::Result<C, Error<T>> {
+        self.with_item_context(op).collect()
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    fn partition_context<
+        C: Default + Extend<O>,
+        T: 'static + Display + Debug,
+        F: FnMut(usize) -> T,
+    >(
+        self,
+        op: F,
+    ) -> (C, Vec<Error<T>>) {
+        let mut values = C::default();
+        let mut errors = Vec::new();
+
+        for item in self.with_item_context(op) {
+            match item {
+                Ok(value) => values.extend(Some(value)),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        (values, errors)
+    }
+}
+
+/// The iterator returned by [`IteratorContext::with_item_context()`](IteratorContext::with_item_context)
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct ContextIter<I, F> {
+    iter: I,
+    op: F,
+    index: usize,
+    occurrence: Option<Occurrence>,
+}
+
+impl<O, E, I, T, F> Iterator for ContextIter<I, F>
+where
+    E: Into<Box<dyn StdError + 'static + Send + Sync>>,
+    I: Iterator<Item = core::result::Result<O, E>>,
+    T: 'static + Display + Debug,
+    F: FnMut(usize) -> T,
+{
+    type Item = core::result::Result<O, Error<T>>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.iter.next()?;
+        let index = self.index;
+        self.index += 1;
+
+        Some(item.map_err(|error_cause| {
+            Error::with_cause(
+                (self.op)(index),
+                Some(Cause::from_error(error_cause)),
+                self.occurrence.clone(),
+            )
+        }))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Convenience methods for a `Future` resolving to `Result<>` to turn the error into a
+/// decorated [`Error`](Error)
+///
+/// The source `Location` is recorded, where the future is built, not where it is awaited.
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::FutureContext as _;
+/// use std::io;
+///
+/// async fn connect() -> Result<(), io::Error> {
+///     Err(io::Error::from(io::ErrorKind::ConnectionRefused))
+/// }
+///
+/// async fn start() -> chainerror::Result<(), &'static str> {
+///     let db = connect().context("connecting to the database");
+///     let cache = connect().with_context(|| "connecting to the cache");
+///     db.await?;
+///     cache.await?;
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "future")]
+pub trait FutureContext<O, E: Into<Box<dyn StdError + 'static + Send + Sync>>>:
+    Future<Output = core::result::Result<O, E>> + Sized
+{
+    /// Decorate the error with a `kind` of type `T` and the source `Location`
+    fn context<T: 'static + Display + Debug>(self, kind: T) -> ContextFuture<Self, T>;
+
+    /// Decorate the error with a `kind` of type `T` produced with `op` and the source `Location`
+    fn with_context<T: 'static + Display + Debug, F: FnOnce() -> T>(
+        self,
+        op: F,
+    ) -> WithContextFuture<Self, F>;
+
+    /// Decorate the error just with the source `Location`
+    fn annotate(self) -> ContextFuture<Self, AnnotatedError>;
+}
+
+#[cfg(feature = "future")]
+impl<O, E, Fut> FutureContext<O, E> for Fut
+where
+    E: Into<Box<dyn StdError + 'static + Send + Sync>>,
+    Fut: Future<Output = core::result::Result<O, E>>,
+{
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn context<T: 'static + Display + Debug>(self, kind: T) -> ContextFuture<Self, T> {
+        ContextFuture {
+            future: self,
+            kind: Some(kind),
+            occurrence: caller_occurrence(),
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn with_context<T: 'static + Display + Debug, F: FnOnce() -> T>(
+        self,
+        op: F,
+    ) -> WithContextFuture<Self, F> {
+        WithContextFuture {
+            future: self,
+            op: Some(op),
+            occurrence: caller_occurrence(),
+        }
+    }
+
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn annotate(self) -> ContextFuture<Self, AnnotatedError> {
+        ContextFuture {
+            future: self,
+            kind: Some(AnnotatedError(())),
+            occurrence: caller_occurrence(),
+        }
+    }
+}
+
+/// The future returned by [`FutureContext::context()`](FutureContext::context)
+/// and [`FutureContext::annotate()`](FutureContext::annotate)
+#[cfg(feature = "future")]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ContextFuture<Fut, T> {
+    future: Fut,
+    kind: Option<T>,
+    occurrence: Option<Occurrence>,
+}
+
+#[cfg(feature = "future")]
+impl<O, E, Fut, T> Future for ContextFuture<Fut, T>
+where
+    E: Into<Box<dyn StdError + 'static + Send + Sync>>,
+    Fut: Future<Output = core::result::Result<O, E>>,
+    T: 'static + Display + Debug,
+{
+    type Output = core::result::Result<O, Error<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is pinned with `self`, the other fields are never pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        // SAFETY: `future` is never moved out of `self`
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        future.poll(cx).map_err(|error_cause| {
+            Error::with_cause(
+                this.kind.take().expect("polled after completion"),
+                Some(Cause::from_error(error_cause)),
+                this.occurrence.take(),
+            )
+        })
+    }
+}
+
+/// The future returned by [`FutureContext::with_context()`](FutureContext::with_context)
+#[cfg(feature = "future")]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WithContextFuture<Fut, F> {
+    future: Fut,
+    op: Option<F>,
+    occurrence: Option<Occurrence>,
+}
+
+#[cfg(feature = "future")]
+impl<O, E, Fut, T, F> Future for WithContextFuture<Fut, F>
+where
+    E: Into<Box<dyn StdError + 'static + Send + Sync>>,
+    Fut: Future<Output = core::result::Result<O, E>>,
+    T: 'static + Display + Debug,
+    F: FnOnce() -> T,
+{
+    type Output = core::result::Result<O, Error<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is pinned with `self`, the other fields are never pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        // SAFETY: `future` is never moved out of `self`
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        future.poll(cx).map_err(|error_cause| {
+            Error::with_cause(
+                (this.op.take().expect("polled after completion"))(),
+                Some(Cause::from_error(error_cause)),
+                this.occurrence.take(),
+            )
+        })
+    }
+}
+
+/// Convenience methods for a `Stream` of `Result<>` items to turn every error into a
+/// decorated [`Error`](Error)
+///
+/// This is the asynchronous equivalent of [`IteratorContext`](IteratorContext).
+/// The source `Location` is recorded, where the stream is built.
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::StreamContext as _;
+/// use futures_core::Stream;
+/// use std::io;
+///
+/// fn ingest(
+///     records: impl Stream<Item = Result<Vec<u8>, io::Error>>,
+/// ) -> impl Stream<Item = chainerror::Result<Vec<u8>, String>> {
+///     records.with_item_context(|index| format!("record #{} of stream \"orders\"", index))
+/// }
+/// ```
+#[cfg(feature = "stream")]
+pub trait StreamContext<O, E: Into<Box<dyn StdError + 'static + Send + Sync>>>:
+    Stream<Item = core::result::Result<O, E>> + Sized
+{
+    /// Decorate every error with a `kind` of type `T` produced with `op` from the index of the
+    /// item and the source `Location`
+    ///
+    /// The index counts all items, not only the errors.
+    fn with_item_context<T: 'static + Display + Debug, F: FnMut(usize) -> T>(
+        self,
+        op: F,
+    ) -> ContextStream<Self, F>;
+}
+
+#[cfg(feature = "stream")]
+impl<O, E, S> StreamContext<O, E> for S
+where
+    E: Into<Box<dyn StdError + 'static + Send + Sync>>,
+    S: Stream<Item = core::result::Result<O, E>>,
+{
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    fn with_item_context<T: 'static + Display + Debug, F: FnMut(usize) -> T>(
+        self,
+        op: F,
+    ) -> ContextStream<Self, F> {
+        ContextStream {
+            stream: self,
+            op,
+            index: 0,
+            occurrence: caller_occurrence(),
+        }
+    }
+}
+
+/// The stream returned by [`StreamContext::with_item_context()`](StreamContext::with_item_context)
+#[cfg(feature = "stream")]
+#[must_use = "streams do nothing unless polled"]
+pub struct ContextStream<S, F> {
+    stream: S,
+    op: F,
+    index: usize,
+    occurrence: Option<Occurrence>,
+}
+
+#[cfg(feature = "stream")]
+impl<O, E, S, T, F> Stream for ContextStream<S, F>
+where
+    E: Into<Box<dyn StdError + 'static + Send + Sync>>,
+    S: Stream<Item = core::result::Result<O, E>>,
+    T: 'static + Display + Debug,
+    F: FnMut(usize) -> T,
+{
+    type Item = core::result::Result<O, Error<T>>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        // SAFETY: `stream` is pinned with `self`, the other fields are never pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        // SAFETY: `stream` is never moved out of `self`
+        let stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+
+        let item = match stream.poll_next(cx) {
+            Poll::Ready(Some(item)) => item,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        let index = this.index;
+        this.index += 1;
+
+        Poll::Ready(Some(item.map_err(|error_cause| {
+            Error::with_cause(
+                (this.op)(index),
+                Some(Cause::from_error(error_cause)),
+                this.occurrence.clone(),
+            )
+        })))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// An iterator over all error causes/sources
+pub struct ErrorIter<'a> {
+    current: Option<&'a (dyn StdError + 'static)>,
+}
+
+impl<'a> Iterator for ErrorIter<'a> {
+    type Item = &'a (dyn StdError + 'static);
 
-    /// Return a reference to T of [`Error<T>`](Error)
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// use chainerror::Context as _;
-    /// use std::error::Error;
-    /// use std::io;
-    ///
-    /// fn do_some_io() -> Result<(), Box<dyn Error + Send + Sync>> {
-    ///     Err(io::Error::from(io::ErrorKind::NotFound))?;
-    ///     Ok(())
-    /// }
-    ///
-    /// chainerror::str_context!(Func2Error);
-    ///
-    /// fn func2() -> Result<(), Box<dyn Error + Send + Sync>> {
-    ///     let filename = "foo.txt";
-    ///     do_some_io().context(Func2Error(format!("Error reading '{}'", filename)))?;
-    ///     Ok(())
-    /// }
-    ///
-    /// #[derive(Debug)]
-    /// enum Func1ErrorKind {
-    ///     Func2,
-    ///     IO(String),
-    /// }
-    ///
-    /// /// impl ::std::fmt::Display for Func1ErrorKind {…}
-    /// # impl ::std::fmt::Display for Func1ErrorKind {
-    /// #     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-    /// #         match self {
-    /// #             Func1ErrorKind::Func2 => write!(f, "func1 error calling func2"),
-    /// #             Func1ErrorKind::IO(filename) => write!(f, "Error reading '{}'", filename),
-    /// #         }
-    /// #     }
-    /// # }
-    ///
-    /// fn func1() -> chainerror::Result<(), Func1ErrorKind> {
-    ///     func2().context(Func1ErrorKind::Func2)?;
-    ///     do_some_io().context(Func1ErrorKind::IO("bar.txt".into()))?;
-    ///     Ok(())
-    /// }
-    ///
-    /// if let Err(e) = func1() {
-    ///     match e.kind() {
-    ///         Func1ErrorKind::Func2 => {}
-    ///         Func1ErrorKind::IO(filename) => panic!(),
-    ///     }
-    /// }
-    /// #    else {
-    /// #         unreachable!();
-    /// #    }
-    /// ```
     #[inline]
-    pub fn kind(&self) -> &T {
-        &self.kind
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.current;
+        self.current = self.current.and_then(StdError::source);
+        current
     }
+}
+
+/// A depth-first iterator over all error causes/sources, which descends into every child of a
+/// [`MultiError`](MultiError)
+///
+/// Only a `MultiError` with more than one child pushes its remaining children on the stack, so
+/// a chain without one is walked without allocating.
+struct TreeIter<'a> {
+    current: Option<&'a (dyn StdError + 'static)>,
+    stack: Vec<core::slice::Iter<'a, Cause>>,
+}
+
+impl<'a> Iterator for TreeIter<'a> {
+    type Item = &'a (dyn StdError + 'static);
 
-    /// Returns an Iterator over all error causes/sources
-    ///
-    /// # Example
     #[inline]
-    pub fn iter(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
-        ErrorIter {
-            current: Some(self),
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.current?;
+        self.current = match current.downcast_ref::<MultiError>() {
+            Some(multi) => {
+                let mut children = multi.errors.iter();
+                let first = children.next();
+                if children.len() > 0 {
+                    self.stack.push(children);
+                }
+                first.map(|e| &**e)
+            }
+            None => current.source(),
+        };
+        while self.current.is_none() {
+            match self.stack.last_mut().map(Iterator::next) {
+                Some(Some(e)) => self.current = Some(&**e),
+                Some(None) => {
+                    self.stack.pop();
+                }
+                None => break,
+            }
         }
+        Some(current)
     }
 }
 
-/// Convenience methods for `Result<>` to turn the error into a decorated [`Error`](Error)
-pub trait Context<O, E: Into<Box<dyn StdError + 'static + Send + Sync>>> {
-    /// Decorate the error with a `kind` of type `T` and the source `Location`
-    fn context<T: 'static + Display + Debug>(self, kind: T) -> std::result::Result<O, Error<T>>;
+/// An aggregate of several independent error chains
+///
+/// The first child is the `source()`, so code only following the `source()` chain sees it.
+/// [`find_cause()`](Error::find_cause) and friends of an [`Error`](Error) search every child.
+///
+/// `{:#}` and `{:?}` render the children as a tree.
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::Context as _;
+/// use chainerror::IteratorContext as _;
+/// use chainerror::MultiError;
+/// use std::io;
+///
+/// fn check(field: &str) -> Result<(), io::Error> {
+///     Err(io::Error::new(io::ErrorKind::InvalidInput, field))
+/// }
+///
+/// fn validate() -> chainerror::Result<(), &'static str> {
+///     let fields = ["name", "email"];
+///     let (_, errors) = fields
+///         .iter()
+///         .map(|field| check(field))
+///         .partition_context::<Vec<()>, _, _>(|index| format!("checking {}", fields[index]));
+///
+///     if !errors.is_empty() {
+///         Err(errors.into_iter().collect::<MultiError>()).context("validating the form")?;
+///     }
+///     Ok(())
+/// }
+///
+/// let err = validate().unwrap_err();
+/// assert_eq!(
+///     format!("{:#}", err),
+///     "\
+/// validating the form
+/// Caused by:
+///   2 errors
+///   ├─ checking name
+///   │  Caused by:
+///   │    name
+///   └─ checking email
+///      Caused by:
+///        email"
+/// );
+/// assert_eq!(err.find_cause::<io::Error>().unwrap().to_string(), "name");
+/// ```
+#[derive(Default)]
+#[cfg_attr(feature = "arc", derive(Clone))]
+pub struct MultiError {
+    errors: Vec<Cause>,
+}
 
-    /// Decorate the error just with the source `Location`
-    fn annotate(self) -> std::result::Result<O, Error<AnnotatedError>>;
+impl MultiError {
+    /// Create an empty `MultiError`
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    /// Decorate the `error` with a `kind` of type `T` produced with a `FnOnce(&error)` and the source `Location`
-    fn map_context<T: 'static + Display + Debug, F: FnOnce(&E) -> T>(
-        self,
-        op: F,
-    ) -> std::result::Result<O, Error<T>>;
-}
+    /// Add the error chain `error` as a child
+    #[inline]
+    pub fn push<E: Into<Box<dyn StdError + 'static + Send + Sync>>>(&mut self, error: E) {
+        self.errors.push(Cause::new(error.into()));
+    }
 
-/// Convenience type to just decorate the error with the source `Location`
-pub struct AnnotatedError(());
+    /// Return the number of children
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
 
-impl Display for AnnotatedError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(passed error)")
+    /// Return `true`, if there are no children
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
     }
-}
 
-impl Debug for AnnotatedError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(passed error)")
+    /// Returns an Iterator over all children
+    #[inline]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(dyn StdError + 'static)> {
+        self.errors.iter().map(|e| &**e)
     }
-}
 
-impl<O, E: Into<Box<dyn StdError + 'static + Send + Sync>>> Context<O, E>
-    for std::result::Result<O, E>
-{
-    #[track_caller]
+    /// Find the first error cause of type U in any of the children, if any exists
     #[inline]
-    fn context<T: 'static + Display + Debug>(self, kind: T) -> std::result::Result<O, Error<T>> {
-        match self {
-            Ok(t) => Ok(t),
-            Err(error_cause) => Err(Error::new(
-                kind,
-                Some(error_cause.into()),
-                Some(Location::caller().to_string()),
-            )),
-        }
+    pub fn find_cause<U: StdError + 'static>(&self) -> Option<&U> {
+        tree(self)
+            .filter_map(<dyn StdError>::downcast_ref::<U>)
+            .next()
     }
 
-    #[track_caller]
+    /// Find the first error cause of type [`Error<U>`](Error) in any of the children,
+    /// if any exists
     #[inline]
-    fn annotate(self) -> std::result::Result<O, Error<AnnotatedError>> {
-        match self {
-            Ok(t) => Ok(t),
-            Err(error_cause) => Err(Error::new(
-                AnnotatedError(()),
-                Some(error_cause.into()),
-                Some(Location::caller().to_string()),
-            )),
+    pub fn find_chain_cause<U: 'static + Display + Debug>(&self) -> Option<&Error<U>> {
+        tree(self)
+            .filter_map(<dyn StdError>::downcast_ref::<Error<U>>)
+            .next()
+    }
+
+    /// Write `self` and every child rendered with `child` as a tree
+    fn fmt_tree(
+        &self,
+        f: &mut Formatter<'_>,
+        child: impl Fn(&(dyn StdError + 'static)) -> String,
+    ) -> core::fmt::Result {
+        write!(f, "{}", self)?;
+
+        let last = self.len().saturating_sub(1);
+        for (i, e) in self.iter().enumerate() {
+            let (first, rest) = if i == last {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+            for (n, line) in child(e).lines().enumerate() {
+                write!(f, "\n{}{}", if n == 0 { first } else { rest }, line)?;
+            }
         }
+
+        Ok(())
     }
+}
 
-    #[track_caller]
+/// Walk `error` and all its error causes/sources, including all children of a
+/// [`MultiError`](MultiError)
+#[inline]
+fn tree<'a>(error: &'a (dyn StdError + 'static)) -> TreeIter<'a> {
+    TreeIter {
+        current: Some(error),
+        stack: Vec::new(),
+    }
+}
+
+impl<E: Into<Box<dyn StdError + 'static + Send + Sync>>> FromIterator<E> for MultiError {
     #[inline]
-    fn map_context<T: 'static + Display + Debug, F: FnOnce(&E) -> T>(
-        self,
-        op: F,
-    ) -> std::result::Result<O, Error<T>> {
-        match self {
-            Ok(t) => Ok(t),
-            Err(error_cause) => {
-                let kind = op(&error_cause);
-                Err(Error::new(
-                    kind,
-                    Some(error_cause.into()),
-                    Some(Location::caller().to_string()),
-                ))
-            }
+    fn from_iter<I: IntoIterator<Item = E>>(iter: I) -> Self {
+        let mut multi = MultiError::new();
+        multi.extend(iter);
+        multi
+    }
+}
+
+impl<E: Into<Box<dyn StdError + 'static + Send + Sync>>> Extend<E> for MultiError {
+    #[inline]
+    fn extend<I: IntoIterator<Item = E>>(&mut self, iter: I) {
+        for error in iter {
+            self.push(error);
         }
     }
 }
 
-/// An iterator over all error causes/sources
-pub struct ErrorIter<'a> {
-    current: Option<&'a (dyn StdError + 'static)>,
+impl StdError for MultiError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.iter().next()
+    }
 }
 
-impl<'a> Iterator for ErrorIter<'a> {
-    type Item = &'a (dyn StdError + 'static);
+impl Display for MultiError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        if f.alternate() {
+            return self.fmt_tree(f, |e| format!("{:#}", e));
+        }
 
+        match self.len() {
+            1 => write!(f, "1 error"),
+            n => write!(f, "{} errors", n),
+        }
+    }
+}
+
+impl Debug for MultiError {
     #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        let current = self.current;
-        self.current = self.current.and_then(StdError::source);
-        current
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        if f.alternate() {
+            return f
+                .debug_struct("MultiError")
+                .field("errors", &self.errors)
+                .finish();
+        }
+
+        self.fmt_tree(f, |e| format!("{:?}", e))
     }
 }
 
-impl<T: 'static + Display + Debug> std::ops::Deref for Error<T> {
+impl<T: 'static + Display + Debug> core::ops::Deref for Error<T> {
     type Target = T;
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        &self.kind
+        &self.inner.kind
     }
 }
 
@@ -327,6 +2699,15 @@ pub trait ErrorDown {
     fn downcast_inner_ref<T: 'static + StdError>(&self) -> Option<&T>;
     /// Downcast to T mutable reference of `Error<T>`
     fn downcast_inner_mut<T: 'static + StdError>(&mut self) -> Option<&mut T>;
+    /// Return the fields of `Error<T>`, or an empty slice, if not of type `Error<T>`
+    fn fields(&self) -> &[Field];
+    /// Return the attachment of type `A` of `Error<T>`, if any and if of type `Error<T>`
+    fn attachment<A: Any + Send + Sync>(&self) -> Option<&A>;
+    /// Classify this error, not looking at the error causes
+    ///
+    /// An attached [`Retryability`](Retryability) decides first, then the built-in
+    /// rules for `std::io::Error` and `Error<std::io::ErrorKind>`.
+    fn retryability(&self) -> Option<Retryability>;
 }
 
 impl<U: 'static + Display + Debug> ErrorDown for Error<U> {
@@ -339,7 +2720,7 @@ impl<U: 'static + Display + Debug> ErrorDown for Error<U> {
     fn downcast_chain_ref<T: 'static + Display + Debug>(&self) -> Option<&Error<T>> {
         if self.is_chain::<T>() {
             // Use transmute when we've verified the types match
-            unsafe { Some(std::mem::transmute::<&Error<U>, &Error<T>>(self)) }
+            unsafe { Some(core::mem::transmute::<&Error<U>, &Error<T>>(self)) }
         } else {
             None
         }
@@ -349,7 +2730,7 @@ impl<U: 'static + Display + Debug> ErrorDown for Error<U> {
     fn downcast_chain_mut<T: 'static + Display + Debug>(&mut self) -> Option<&mut Error<T>> {
         if self.is_chain::<T>() {
             // Use transmute when we've verified the types match
-            unsafe { Some(std::mem::transmute::<&mut Error<U>, &mut Error<T>>(self)) }
+            unsafe { Some(core::mem::transmute::<&mut Error<U>, &mut Error<T>>(self)) }
         } else {
             None
         }
@@ -358,7 +2739,7 @@ impl<U: 'static + Display + Debug> ErrorDown for Error<U> {
     fn downcast_inner_ref<T: 'static + StdError>(&self) -> Option<&T> {
         if self.is_chain::<T>() {
             // Use transmute when we've verified the types match
-            unsafe { Some(std::mem::transmute::<&U, &T>(&self.kind)) }
+            unsafe { Some(core::mem::transmute::<&U, &T>(&self.inner.kind)) }
         } else {
             None
         }
@@ -368,11 +2749,26 @@ impl<U: 'static + Display + Debug> ErrorDown for Error<U> {
     fn downcast_inner_mut<T: 'static + StdError>(&mut self) -> Option<&mut T> {
         if self.is_chain::<T>() {
             // Use transmute when we've verified the types match
-            unsafe { Some(std::mem::transmute::<&mut U, &mut T>(&mut self.kind)) }
+            unsafe { Some(core::mem::transmute::<&mut U, &mut T>(&mut self.inner.kind)) }
         } else {
             None
         }
     }
+
+    #[inline]
+    fn fields(&self) -> &[Field] {
+        self.inner.layer.fields()
+    }
+
+    #[inline]
+    fn attachment<A: Any + Send + Sync>(&self) -> Option<&A> {
+        self.inner.layer.attachment()
+    }
+
+    #[inline]
+    fn retryability(&self) -> Option<Retryability> {
+        retryability_of(self, &Classifiers::new())
+    }
 }
 
 impl ErrorDown for dyn StdError + 'static {
@@ -406,6 +2802,21 @@ impl ErrorDown for dyn StdError + 'static {
         self.downcast_mut::<Error<T>>()
             .and_then(|e| e.downcast_inner_mut::<T>())
     }
+
+    #[inline]
+    fn fields(&self) -> &[Field] {
+        Layer::of(self).map_or(&[], Layer::fields)
+    }
+
+    #[inline]
+    fn attachment<A: Any + Send + Sync>(&self) -> Option<&A> {
+        Layer::of(self).and_then(Layer::attachment)
+    }
+
+    #[inline]
+    fn retryability(&self) -> Option<Retryability> {
+        retryability_of(self, &Classifiers::new())
+    }
 }
 
 impl ErrorDown for dyn StdError + 'static + Send {
@@ -439,97 +2850,408 @@ impl ErrorDown for dyn StdError + 'static + Send {
         self.downcast_mut::<Error<T>>()
             .and_then(|e| e.downcast_inner_mut::<T>())
     }
-}
 
-impl ErrorDown for dyn StdError + 'static + Send + Sync {
     #[inline]
-    fn is_chain<T: 'static + Display + Debug>(&self) -> bool {
-        self.is::<Error<T>>()
+    fn fields(&self) -> &[Field] {
+        Layer::of(self).map_or(&[], Layer::fields)
+    }
+
+    #[inline]
+    fn attachment<A: Any + Send + Sync>(&self) -> Option<&A> {
+        Layer::of(self).and_then(Layer::attachment)
+    }
+
+    #[inline]
+    fn retryability(&self) -> Option<Retryability> {
+        retryability_of(self, &Classifiers::new())
+    }
+}
+
+impl ErrorDown for dyn StdError + 'static + Send + Sync {
+    #[inline]
+    fn is_chain<T: 'static + Display + Debug>(&self) -> bool {
+        self.is::<Error<T>>()
+    }
+
+    #[inline]
+    fn downcast_chain_ref<T: 'static + Display + Debug>(&self) -> Option<&Error<T>> {
+        self.downcast_ref::<Error<T>>()
+    }
+
+    #[inline]
+    fn downcast_chain_mut<T: 'static + Display + Debug>(&mut self) -> Option<&mut Error<T>> {
+        self.downcast_mut::<Error<T>>()
+    }
+
+    #[inline]
+    fn downcast_inner_ref<T: 'static + StdError>(&self) -> Option<&T> {
+        self.downcast_ref::<T>()
+            .or_else(|| self.downcast_ref::<Error<T>>().map(|e| e.kind()))
+    }
+
+    #[inline]
+    fn downcast_inner_mut<T: 'static + StdError>(&mut self) -> Option<&mut T> {
+        if self.is::<T>() {
+            return self.downcast_mut::<T>();
+        }
+
+        self.downcast_mut::<Error<T>>()
+            .and_then(|e| e.downcast_inner_mut::<T>())
+    }
+
+    #[inline]
+    fn fields(&self) -> &[Field] {
+        Layer::of(self).map_or(&[], Layer::fields)
+    }
+
+    #[inline]
+    fn attachment<A: Any + Send + Sync>(&self) -> Option<&A> {
+        Layer::of(self).and_then(Layer::attachment)
+    }
+
+    #[inline]
+    fn retryability(&self) -> Option<Retryability> {
+        retryability_of(self, &Classifiers::new())
+    }
+}
+
+/// A stable code of an error kind, e.g. `CFG-0012`, which is published to customers
+///
+/// Use the [`ErrorCodes`](ErrorCodes) registry to find the codes in an error chain and to
+/// catch duplicate codes.
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::ErrorCode;
+///
+/// #[derive(Debug)]
+/// enum ConfigError {
+///     Missing,
+///     Invalid,
+/// }
+/// # impl std::fmt::Display for ConfigError {
+/// #     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// #         write!(f, "{:?}", self)
+/// #     }
+/// # }
+///
+/// impl ErrorCode for ConfigError {
+///     const CODES: &'static [&'static str] = &["CFG-0011", "CFG-0012"];
+///
+///     fn code(&self) -> &'static str {
+///         match self {
+///             ConfigError::Missing => "CFG-0011",
+///             ConfigError::Invalid => "CFG-0012",
+///         }
+///     }
+/// }
+///
+/// let err = chainerror::Error::from(ConfigError::Invalid);
+/// assert_eq!(err.code(), "CFG-0012");
+/// ```
+pub trait ErrorCode {
+    /// All codes, which [`code()`](ErrorCode::code) can return
+    const CODES: &'static [&'static str];
+
+    /// Return the code of the error
+    fn code(&self) -> &'static str;
+}
+
+impl<T: ErrorCode> ErrorCode for Error<T> {
+    const CODES: &'static [&'static str] = T::CODES;
+
+    #[inline]
+    fn code(&self) -> &'static str {
+        self.inner.kind.code()
+    }
+}
+
+/// A registry of the kinds implementing [`ErrorCode`](ErrorCode)
+///
+/// The codes are found for every [`Error<K>`](Error) of a registered kind `K` in the chain,
+/// also if it is wrapped in a type created with [`err_kind!`](err_kind).
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::Context as _;
+/// use chainerror::ErrorCodes;
+///
+/// chainerror::str_context!(ReadError, "IO-0001");
+/// chainerror::str_context!(ConfigError, "CFG-0012");
+///
+/// let mut codes = ErrorCodes::new();
+/// codes.register::<ReadError>().unwrap();
+/// codes.register::<ConfigError>().unwrap();
+///
+/// let err = Err::<(), _>("not found")
+///     .context(ReadError::new("reading foo.toml"))
+///     .context(ConfigError::new("loading the config"))
+///     .unwrap_err();
+///
+/// assert_eq!(codes.find_code(&err), Some("CFG-0012"));
+/// assert_eq!(codes.root_code(&err), Some("IO-0001"));
+///
+/// chainerror::str_context!(OtherError, "CFG-0012");
+/// assert!(codes.register::<OtherError>().is_err());
+/// ```
+#[derive(Default)]
+pub struct ErrorCodes {
+    kinds: Vec<CodedKind>,
+}
+
+struct CodedKind {
+    type_id: TypeId,
+    type_name: &'static str,
+    codes: &'static [&'static str],
+    code_of: fn(&(dyn StdError + 'static)) -> Option<&'static str>,
+}
+
+impl ErrorCodes {
+    /// Create an empty registry
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the kind `K`, failing if one of its codes is already used by another kind
+    pub fn register<K: ErrorCode + 'static + Display + Debug>(
+        &mut self,
+    ) -> core::result::Result<&mut Self, DuplicateCode> {
+        let type_id = TypeId::of::<K>();
+
+        if self.kinds.iter().any(|k| k.type_id == type_id) {
+            return Ok(self);
+        }
+
+        for (i, code) in K::CODES.iter().enumerate() {
+            let used_by = self
+                .kinds
+                .iter()
+                .find(|k| k.codes.contains(code))
+                .map(|k| k.type_name)
+                .or_else(|| K::CODES[..i].contains(code).then(core::any::type_name::<K>));
+
+            if let Some(used_by) = used_by {
+                return Err(DuplicateCode {
+                    code,
+                    kind: core::any::type_name::<K>(),
+                    used_by,
+                });
+            }
+        }
+
+        self.kinds.push(CodedKind {
+            type_id,
+            type_name: core::any::type_name::<K>(),
+            codes: K::CODES,
+            code_of: |e| Inner::<K>::of(e).map(|inner| inner.kind.code()),
+        });
+
+        Ok(self)
+    }
+
+    /// Return the code of the outermost error in the chain with a registered kind
+    #[inline]
+    pub fn find_code(&self, error: &(dyn StdError + 'static)) -> Option<&'static str> {
+        self.codes(error).next()
     }
 
+    /// Return the code of the innermost error in the chain with a registered kind
     #[inline]
-    fn downcast_chain_ref<T: 'static + Display + Debug>(&self) -> Option<&Error<T>> {
-        self.downcast_ref::<Error<T>>()
+    pub fn root_code(&self, error: &(dyn StdError + 'static)) -> Option<&'static str> {
+        self.codes(error).last()
     }
 
-    #[inline]
-    fn downcast_chain_mut<T: 'static + Display + Debug>(&mut self) -> Option<&mut Error<T>> {
-        self.downcast_mut::<Error<T>>()
+    /// Returns an Iterator over the codes of all errors in the chain with a registered kind
+    pub fn codes<'a>(
+        &'a self,
+        error: &'a (dyn StdError + 'static),
+    ) -> impl Iterator<Item = &'static str> + 'a {
+        ErrorIter {
+            current: Some(error),
+        }
+        .filter_map(move |e| self.kinds.iter().find_map(|k| (k.code_of)(e)))
     }
+}
 
-    #[inline]
-    fn downcast_inner_ref<T: 'static + StdError>(&self) -> Option<&T> {
-        self.downcast_ref::<T>()
-            .or_else(|| self.downcast_ref::<Error<T>>().map(|e| e.kind()))
+impl Debug for ErrorCodes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_map()
+            .entries(self.kinds.iter().map(|k| (k.type_name, k.codes)))
+            .finish()
+    }
+}
+
+/// The error returned by [`ErrorCodes::register()`](ErrorCodes::register) for a code,
+/// which is already used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCode {
+    /// The duplicate code
+    pub code: &'static str,
+    /// The type name of the kind, which was registered
+    pub kind: &'static str,
+    /// The type name of the kind, which already uses the code
+    pub used_by: &'static str,
+}
+
+impl Display for DuplicateCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "error code `{}` of `{}` is already used by `{}`",
+            self.code, self.kind, self.used_by
+        )
     }
+}
+
+impl StdError for DuplicateCode {}
 
+/// With the `arc` feature, the error cause is shared between the clones
+#[cfg(feature = "arc")]
+impl<T: Clone> Clone for Error<T> {
     #[inline]
-    fn downcast_inner_mut<T: 'static + StdError>(&mut self) -> Option<&mut T> {
-        if self.is::<T>() {
-            return self.downcast_mut::<T>();
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
         }
-
-        self.downcast_mut::<Error<T>>()
-            .and_then(|e| e.downcast_inner_mut::<T>())
     }
 }
 
 impl<T: 'static + Display + Debug> StdError for Error<T> {
     #[inline]
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        self.error_cause
-            .as_ref()
-            .map(|e| e.as_ref() as &(dyn StdError + 'static))
+        self.inner.layer.error_cause.as_deref()
+    }
+
+    // Return the `Repr<T>`, so the layer and the kind of any `dyn Error` are found, see
+    // `inner_of()`
+    #[inline]
+    fn cause(&self) -> Option<&dyn StdError> {
+        Some(&self.inner)
     }
 }
 
 impl<T: 'static + Display + Debug> StdError for &mut Error<T> {
     #[inline]
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        self.error_cause
-            .as_ref()
-            .map(|e| e.as_ref() as &(dyn StdError + 'static))
+        self.inner.layer.error_cause.as_deref()
+    }
+
+    #[inline]
+    fn cause(&self) -> Option<&dyn StdError> {
+        Some(&self.inner)
     }
 }
 
 impl<T: 'static + Display + Debug> Display for Error<T> {
     #[inline]
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.kind)?;
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        if !f.alternate() {
+            return write!(f, "{}", self.inner.kind);
+        }
 
-        if f.alternate() {
-            if let Some(e) = self.source() {
-                write!(f, "\nCaused by:\n  {:#}", &e)?;
+        if let Some(severity) = self.inner.layer.attachment::<Severity>() {
+            write!(f, "{}: ", severity)?;
+        }
+        write!(f, "{}", self.inner.kind)?;
+
+        // Walk the chain here instead of recursing, so the hints are only shown once
+        let mut current = self.source();
+        while let Some(e) = current {
+            write!(f, "\nCaused by:\n  ")?;
+
+            match Layer::of(e) {
+                Some(layer) => {
+                    if let Some(severity) = layer.attachment::<Severity>() {
+                        write!(f, "{}: ", severity)?;
+                    }
+                    write!(f, "{}", e)?;
+                    current = e.source();
+                }
+                None => {
+                    // Indent multi-line errors like the tree of a `MultiError`
+                    write!(f, "{}", format!("{:#}", e).replace('\n', "\n  "))?;
+                    current = None;
+                }
             }
         }
 
+        let layers = self.iter().filter_map(Layer::of).collect::<Vec<_>>();
+        for hint in layers.iter().rev().flat_map(|layer| layer.hints()) {
+            write!(f, "\n{}", hint)?;
+        }
+
         Ok(())
     }
 }
 
 impl<T: 'static + Display + Debug> Debug for Error<T> {
+    #[cfg_attr(feature = "backtrace", clippy::msrv = "1.65")]
     #[inline]
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         if f.alternate() {
-            let mut f = f.debug_struct(&format!("Error<{}>", std::any::type_name::<T>()));
+            let mut f = f.debug_struct(&format!("Error<{}>", core::any::type_name::<T>()));
 
             let f = f
-                .field("occurrence", &self.occurrence)
-                .field("kind", &self.kind)
-                .field("source", &self.source());
+                .field("occurrence", &self.inner.layer.occurrence)
+                .field("kind", &self.inner.kind);
+
+            if let Some(severity) = self.inner.layer.attachment::<Severity>() {
+                f.field("severity", severity);
+            }
+
+            if !self.inner.layer.fields().is_empty() {
+                f.field("fields", &Fields(self.inner.layer.fields()));
+            }
+
+            if !self.inner.layer.hints().is_empty() {
+                f.field("hints", &self.inner.layer.hints());
+            }
+
+            if !self.inner.layer.suppressed().is_empty() {
+                f.field("suppressed", &self.inner.layer.suppressed());
+            }
+
+            #[cfg(feature = "timestamp")]
+            if let Some(cause) = self.source().and_then(Layer::of) {
+                let elapsed = self
+                    .inner
+                    .layer
+                    .captured
+                    .instant
+                    .saturating_duration_since(cause.captured.instant);
+                f.field("elapsed", &format_args!("+{:?}", elapsed));
+            }
+
+            #[cfg(feature = "thread")]
+            f.field("thread", &self.inner.layer.captured.thread);
+
+            f.field("source", &self.source());
+
+            #[cfg(feature = "backtrace")]
+            if self.inner.layer.captured.backtrace.status() == BacktraceStatus::Captured {
+                f.field("backtrace", &self.inner.layer.captured.backtrace);
+            }
+
+            #[cfg(feature = "tracing")]
+            if self.inner.layer.captured.span_trace.status() == SpanTraceStatus::CAPTURED {
+                f.field("span_trace", &self.inner.layer.captured.span_trace);
+            }
 
             f.finish()
         } else {
-            if let Some(ref o) = self.occurrence {
+            if let Some(ref o) = self.inner.layer.occurrence {
                 write!(f, "{}: ", o)?;
             }
 
             if TypeId::of::<String>() == TypeId::of::<T>()
                 || TypeId::of::<&str>() == TypeId::of::<T>()
             {
-                Display::fmt(&self.kind, f)?;
+                Display::fmt(&self.inner.kind, f)?;
             } else {
-                Debug::fmt(&self.kind, f)?;
+                Debug::fmt(&self.inner.kind, f)?;
             }
 
             if let Some(e) = self.source() {
@@ -544,14 +3266,16 @@ impl<T> From<T> for Error<T>
 where
     T: 'static + Display + Debug,
 {
-    #[track_caller]
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
     #[inline]
     fn from(e: T) -> Error<T> {
-        Error::new(e, None, Some(Location::caller().to_string()))
+        Error::new_with_occurrence(e, None, caller_occurrence())
     }
 }
 /// Convenience macro to create a "new type" T(String) and implement Display + Debug for T
 ///
+/// With `str_context!(T, "CODE")`, T also implements [`ErrorCode`](ErrorCode) with the code `"CODE"`.
+///
 /// # Examples
 ///
 /// ```rust
@@ -593,24 +3317,33 @@ where
 macro_rules! str_context {
     ($e:ident) => {
         #[derive(Clone)]
-        pub struct $e(pub String);
+        pub struct $e(pub $crate::__private::String);
         impl $e {
             #[allow(dead_code)]
-            pub fn new<S: Into<String>>(s: S) -> Self {
+            pub fn new<S: Into<$crate::__private::String>>(s: S) -> Self {
                 $e(s.into())
             }
         }
-        impl ::std::fmt::Display for $e {
-            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        impl ::core::fmt::Display for $e {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                 write!(f, "{}", self.0)
             }
         }
-        impl ::std::fmt::Debug for $e {
-            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        impl ::core::fmt::Debug for $e {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                 write!(f, "{}({})", stringify!($e), self.0)
             }
         }
-        impl ::std::error::Error for $e {}
+        impl $crate::__private::StdError for $e {}
+    };
+    ($e:ident, $code:literal) => {
+        $crate::str_context!($e);
+        impl $crate::ErrorCode for $e {
+            const CODES: &'static [&'static str] = &[$code];
+            fn code(&self) -> &'static str {
+                $code
+            }
+        }
     };
 }
 
@@ -622,6 +3355,9 @@ macro_rules! str_context {
 /// Error::kind() returns the ErrorKind
 /// Error::source() returns the parent error
 ///
+/// With `err_kind!(Error, ErrorKind, ErrorCode)`, Error also implements
+/// [`ErrorCode`](ErrorCode) with the codes of the `impl ErrorCode for ErrorKind`.
+///
 /// # Examples
 ///
 /// ```rust
@@ -689,113 +3425,1050 @@ macro_rules! err_kind {
     ($e:ident, $k:ident) => {
         pub struct $e($crate::Error<$k>);
 
-        impl $e {
-            pub fn kind(&self) -> &$k {
-                self.0.kind()
+        impl $e {
+            pub fn kind(&self) -> &$k {
+                self.0.kind()
+            }
+        }
+
+        impl From<$k> for $e {
+            fn from(e: $k) -> Self {
+                $e($crate::Error::new(e, None, None))
+            }
+        }
+
+        impl From<$crate::Error<$k>> for $e {
+            fn from(e: $crate::Error<$k>) -> Self {
+                $e(e)
+            }
+        }
+
+        impl From<&$e> for $k
+        where
+            $k: Clone,
+        {
+            fn from(e: &$e) -> Self {
+                e.kind().clone()
+            }
+        }
+
+        impl $crate::__private::StdError for $e {
+            fn source(&self) -> Option<&(dyn $crate::__private::StdError + 'static)> {
+                self.0.source()
+            }
+
+            #[allow(deprecated)]
+            fn cause(&self) -> Option<&dyn $crate::__private::StdError> {
+                self.0.cause()
+            }
+        }
+
+        impl ::core::fmt::Display for $e {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                ::core::fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl ::core::fmt::Debug for $e {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                ::core::fmt::Debug::fmt(&self.0, f)
+            }
+        }
+    };
+    ($e:ident, $k:ident, ErrorCode) => {
+        $crate::err_kind!($e, $k);
+        impl $crate::ErrorCode for $e {
+            const CODES: &'static [&'static str] = <$k as $crate::ErrorCode>::CODES;
+            fn code(&self) -> &'static str {
+                $crate::ErrorCode::code(self.kind())
+            }
+        }
+    };
+}
+
+/// Create an [`Error<String>`](Error) without an error cause from `format!` arguments and the
+/// source `Location` of the macro call
+///
+/// # Examples
+///
+/// ```rust
+/// use std::error::Error as _;
+///
+/// let path = "foo.txt";
+/// let err = chainerror::format_context!("unsupported format of {}", path);
+///
+/// assert_eq!(err.kind(), "unsupported format of foo.txt");
+/// assert!(err.source().is_none());
+/// ```
+#[macro_export]
+macro_rules! format_context {
+    ($($arg:tt)+) => {
+        $crate::Error::new_with_occurrence(
+            $crate::__private::format!($($arg)+),
+            ::core::option::Option::None,
+            $crate::__private::caller_occurrence(),
+        )
+    };
+}
+
+/// Return early with an [`Error`](Error) of a `kind` and the source `Location` of the macro call
+///
+/// With a string literal and optional `format!` arguments, the kind is the formatted `String`,
+/// even without any arguments.
+/// The error is converted with `From`, so the function can also return a type created with
+/// [`err_kind!`](err_kind) or a `Box<dyn Error>`.
+///
+/// # Examples
+///
+/// ```rust
+/// use chainerror::Context as _;
+///
+/// chainerror::str_context!(ConfigError);
+///
+/// fn parse(line: &str) -> chainerror::Result<u16, ConfigError> {
+///     if line.is_empty() {
+///         chainerror::bail!(ConfigError::new("empty line"));
+///     }
+///     line.parse().context(ConfigError::new("invalid port"))
+/// }
+///
+/// fn load(path: &str) -> chainerror::Result<u16, String> {
+///     if !path.ends_with(".conf") {
+///         chainerror::bail!("unsupported config file {}", path);
+///     }
+///     parse("").context(format!("loading {}", path))
+/// }
+///
+/// assert_eq!(parse("").unwrap_err().kind().0, "empty line");
+/// assert_eq!(load("foo.toml").unwrap_err().kind(), "unsupported config file foo.toml");
+/// ```
+#[macro_export]
+macro_rules! bail {
+    ($fmt:literal $(, $($arg:tt)*)?) => {
+        return ::core::result::Result::Err(::core::convert::From::from(
+            $crate::format_context!($fmt $(, $($arg)*)?),
+        ))
+    };
+    ($kind:expr $(,)?) => {
+        return ::core::result::Result::Err(::core::convert::From::from(
+            $crate::Error::new_with_occurrence(
+                $kind,
+                ::core::option::Option::None,
+                $crate::__private::caller_occurrence(),
+            ),
+        ))
+    };
+}
+
+/// Return early with an [`Error`](Error) like [`bail!`](bail), if the condition is not `true`
+///
+/// # Examples
+///
+/// ```rust
+/// fn connect(port: u16) -> chainerror::Result<(), String> {
+///     chainerror::ensure!(port >= 1024, "port {} is privileged", port);
+///     Ok(())
+/// }
+///
+/// fn listen(backlog: u32) -> chainerror::Result<(), String> {
+///     chainerror::ensure!(backlog > 0, "backlog must not be zero");
+///     Ok(())
+/// }
+///
+/// assert_eq!(connect(80).unwrap_err().kind(), "port 80 is privileged");
+/// assert!(connect(8080).is_ok());
+/// assert!(listen(0).is_err());
+/// ```
+#[macro_export]
+macro_rules! ensure {
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::bail!($($arg)+);
+        }
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::format;
+    pub use alloc::string::String;
+    #[cfg(not(feature = "std"))]
+    pub use core::error::Error as StdError;
+    #[cfg(feature = "std")]
+    pub use std::error::Error as StdError;
+
+    /// Return the [`Occurrence`](crate::Occurrence) of the macro call
+    #[cfg_attr(not(chainerror_no_location), track_caller)]
+    #[inline]
+    pub fn caller_occurrence() -> Option<crate::Occurrence> {
+        crate::caller_occurrence()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Context as _;
+    use super::Decorate as _;
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn test_error_chain_with_multiple_causes() {
+        // Create a chain of errors
+        let io_error = io::Error::new(io::ErrorKind::NotFound, "file not found");
+
+        str_context!(Level3Error);
+        str_context!(Level2Error);
+        str_context!(Level1Error);
+
+        let err = Result::<(), _>::Err(io_error.into())
+            .context(Level3Error("level 3".into()))
+            .context(Level2Error("level 2".into()))
+            .context(Level1Error("level 1".into()))
+            .unwrap_err();
+
+        // Test the error chain
+        assert!(err.is_chain::<Level1Error>());
+        assert!(err.find_chain_cause::<Level2Error>().is_some());
+        assert!(err.find_chain_cause::<Level3Error>().is_some());
+        assert!(err.find_chain_cause::<io::Error>().is_some());
+    }
+
+    #[test]
+    fn test_error_root_cause() {
+        let io_error = io::Error::new(io::ErrorKind::NotFound, "file not found");
+
+        str_context!(WrapperError);
+        let err = Result::<(), _>::Err(io_error.into())
+            .context(WrapperError("wrapper".into()))
+            .unwrap_err();
+
+        let root = err.root_cause().unwrap();
+        assert!(root.is_chain::<io::Error>());
+    }
+
+    #[test]
+    fn test_error_display_and_debug() {
+        str_context!(CustomError);
+        let err = Error::new(
+            CustomError("test error".into()),
+            None,
+            Some("src/lib.rs:100".into()),
+        );
+
+        // Test Display formatting
+        assert_eq!(format!("{}", err), "test error");
+
+        // Test alternate Display formatting
+        assert_eq!(format!("{:#}", err), "test error");
+
+        // Test Debug formatting
+        let debug_output = format!("{:?}", err);
+        assert!(debug_output.contains("test error"));
+        assert!(debug_output.contains("src/lib.rs:100"));
+    }
+
+    #[test]
+    fn test_error_occurrence() {
+        let io_error = io::Error::new(io::ErrorKind::NotFound, "file not found");
+        #[cfg(not(chainerror_no_location))]
+        let line = line!() + 2;
+        let err = std::result::Result::<(), _>::Err(io_error)
+            .context("reading")
+            .unwrap_err();
+
+        #[cfg(not(chainerror_no_location))]
+        {
+            assert_eq!(err.file(), Some(file!()));
+            assert_eq!(err.line(), Some(line));
+            assert!(err.column().is_some());
+            assert!(matches!(err.occurrence(), Some(Occurrence::Location(_))));
+            assert!(format!("{:?}", err).starts_with(&format!("{}:{}:", file!(), line)));
+        }
+        #[cfg(chainerror_no_location)]
+        assert!(err.occurrence().is_none());
+
+        let err = Error::new("custom", None, Some("somewhere".into()));
+        assert_eq!(
+            err.occurrence(),
+            Some(&Occurrence::Custom("somewhere".into()))
+        );
+        assert_eq!(err.file(), None);
+        assert_eq!(err.line(), None);
+        assert_eq!(err.column(), None);
+        assert_eq!(format!("{:?}", err), "somewhere: custom");
+
+        let err = Error::new("none", None, None);
+        assert!(err.occurrence().is_none());
+        assert_eq!(format!("{:?}", err), "none");
+    }
+
+    #[cfg(chainerror_no_location)]
+    #[test]
+    fn test_error_no_location() {
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .context("reading")
+            .annotate()
+            .unwrap_err();
+
+        assert!(err
+            .iter()
+            .filter_map(Layer::of)
+            .all(|l| l.occurrence.is_none()));
+        assert_eq!(
+            format!("{:?}", err),
+            "(passed error)\nCaused by:\nreading\nCaused by:\nKind(NotFound)"
+        );
+        assert!(Error::from("from").occurrence().is_none());
+    }
+
+    #[test]
+    fn test_error_into_parts() {
+        str_context!(OuterError);
+        str_context!(InnerError);
+
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .context(InnerError::new("inner"))
+            .unwrap_err();
+        let line = err.line();
+
+        let err = err.map_kind(|k| OuterError(format!("outer: {}", k)));
+        assert_eq!(err.kind().0, "outer: inner");
+        assert_eq!(err.line(), line);
+        assert_eq!(err.iter().count(), 2);
+        assert!(err.find_cause::<io::Error>().is_some());
+
+        let err = err.with_source(io::Error::from(io::ErrorKind::TimedOut));
+        assert_eq!(err.line(), line);
+        assert_eq!(
+            err.find_cause::<io::Error>().unwrap().kind(),
+            io::ErrorKind::TimedOut
+        );
+
+        let (kind, source, occurrence) = err.into_parts();
+        assert_eq!(kind.0, "outer: inner");
+        assert_eq!(occurrence.and_then(|o| o.line()), line);
+        let source: Cause = source.unwrap();
+        assert!(source.is::<io::Error>());
+
+        // the cause keeps its type, when it is stored again
+        let err = Error::new(InnerError::new("again"), None, None).with_source(source);
+        assert!(err.source().unwrap().is::<io::Error>());
+        let source = err
+            .into_source()
+            .unwrap()
+            .downcast::<InnerError>()
+            .unwrap_err();
+        assert_eq!(
+            source.downcast::<io::Error>().unwrap().kind(),
+            io::ErrorKind::TimedOut
+        );
+
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .context(InnerError::new("inner"))
+            .context("outer")
+            .unwrap_err();
+        let source = err.into_source().unwrap();
+        assert!(source.is_chain::<InnerError>());
+        let source = source.downcast::<io::Error>().unwrap_err();
+        let source = source.downcast::<Error<InnerError>>().unwrap();
+        assert_eq!(source.kind().0, "inner");
+        assert!(source.find_cause::<io::Error>().is_some());
+
+        let err = Error::new(InnerError::new("inner"), None, None);
+        assert!(err.into_source().is_none());
+
+        let err = Error::new(InnerError::new("inner"), None, None);
+        assert_eq!(err.into_kind().0, "inner");
+    }
+
+    #[test]
+    fn test_error_fields() {
+        let path = std::path::PathBuf::from("foo.txt");
+
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .context_with_fields("reading", &[("path", &path), ("uid", &1000)])
+            .annotate()
+            .map_err(|e| e.field("user", "alice"))
+            .unwrap_err();
+
+        assert_eq!(err.fields(), &[Field::new("user", "alice")]);
+
+        let fields = err
+            .iter()
+            .flat_map(|e| e.fields())
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, ["user=\"alice\"", "path=\"foo.txt\"", "uid=1000"]);
+
+        let debug = format!("{:#?}", err);
+        assert!(debug.contains("fields: {\n        \"user\": \"alice\",\n    },"));
+        assert!(debug.contains("\"path\": \"foo.txt\","));
+        assert!(!format!("{:?}", err).contains("alice"));
+    }
+
+    #[test]
+    fn test_error_fields_of_foreign_errors() {
+        #[derive(Debug)]
+        struct Empty;
+
+        impl Display for Empty {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "empty")
             }
         }
 
-        impl From<$k> for $e {
-            fn from(e: $k) -> Self {
-                $e($crate::Error::new(e, None, None))
+        impl StdError for Empty {}
+
+        let empty: Box<dyn StdError + Send + Sync> = Box::new(Empty);
+        assert!(empty.fields().is_empty());
+
+        let io_error: Box<dyn StdError + Send + Sync> = Box::new(io::Error::other("error"));
+        assert!(io_error.fields().is_empty());
+
+        err_kind!(WrappedError, TestErrorKind);
+        let wrapped: Box<dyn StdError + Send + Sync> = Box::new(WrappedError::from(
+            Error::new(TestErrorKind::Basic("test".into()), None, None).field("key", &1),
+        ));
+        assert_eq!(wrapped.fields(), &[Field::new("key", &1)]);
+
+        #[derive(Debug)]
+        struct Outer(Error<&'static str>);
+
+        impl Display for Outer {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "outer")
             }
         }
 
-        impl From<$crate::Error<$k>> for $e {
-            fn from(e: $crate::Error<$k>) -> Self {
-                $e(e)
+        impl StdError for Outer {
+            fn source(&self) -> Option<&(dyn StdError + 'static)> {
+                Some(&self.0)
             }
         }
 
-        impl From<&$e> for $k
-        where
-            $k: Clone,
-        {
-            fn from(e: &$e) -> Self {
-                e.kind().clone()
+        // the layer of the source is not the layer of the foreign error
+        let outer: Box<dyn StdError + Send + Sync> =
+            Box::new(Outer(Error::new("inner", None, None).field("key", &1)));
+        assert!(outer.fields().is_empty());
+        assert_eq!(outer.source().unwrap().fields(), &[Field::new("key", &1)]);
+    }
+
+    #[test]
+    fn test_error_attachments() {
+        #[derive(Debug, PartialEq)]
+        struct RequestId(u64);
+        #[derive(Debug, PartialEq)]
+        struct StatusHint(u16);
+
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .annotate()
+            .attach(StatusHint(404))
+            .context("inner")
+            .attach(RequestId(1))
+            .attach(RequestId(2))
+            .context("outer")
+            .attach(StatusHint(500))
+            .unwrap_err();
+
+        assert_eq!(err.attachment::<StatusHint>(), Some(&StatusHint(500)));
+        assert_eq!(err.attachment::<RequestId>(), None);
+        assert_eq!(err.request::<RequestId>(), Some(&RequestId(2)));
+        assert_eq!(err.request::<StatusHint>(), Some(&StatusHint(500)));
+        assert_eq!(
+            err.request_all::<StatusHint>().collect::<Vec<_>>(),
+            [&StatusHint(500), &StatusHint(404)]
+        );
+        assert_eq!(err.request_all::<RequestId>().count(), 1);
+        assert!(err.request::<String>().is_none());
+
+        let inner = err.source().unwrap();
+        assert_eq!(inner.attachment::<RequestId>(), Some(&RequestId(2)));
+        assert!(inner.source().unwrap().is_chain::<AnnotatedError>());
+        assert!(inner
+            .source()
+            .unwrap()
+            .source()
+            .unwrap()
+            .attachment::<StatusHint>()
+            .is_none());
+    }
+
+    #[test]
+    fn test_error_codes() {
+        str_context!(ReadError, "IO-0001");
+        str_context!(ConfigError, "CFG-0012");
+        str_context!(Uncoded);
+        #[derive(Debug, Clone)]
+        enum CodedKind {
+            NotFound,
+            Invalid,
+        }
+        impl Display for CodedKind {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{:?}", self)
             }
         }
-
-        impl std::error::Error for $e {
-            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-                self.0.source()
+        impl ErrorCode for CodedKind {
+            const CODES: &'static [&'static str] = &["TST-0001", "TST-0002"];
+            fn code(&self) -> &'static str {
+                match self {
+                    CodedKind::NotFound => "TST-0001",
+                    CodedKind::Invalid => "TST-0002",
+                }
             }
         }
+        err_kind!(WrappedError, CodedKind, ErrorCode);
+        assert_eq!(WrappedError::CODES, CodedKind::CODES);
+
+        let mut codes = ErrorCodes::new();
+        codes
+            .register::<ReadError>()
+            .unwrap()
+            .register::<ConfigError>()
+            .unwrap()
+            .register::<CodedKind>()
+            .unwrap()
+            .register::<ReadError>()
+            .unwrap();
+
+        str_context!(DuplicateError, "CFG-0012");
+        let duplicate = codes.register::<DuplicateError>().unwrap_err();
+        assert_eq!(duplicate.code, "CFG-0012");
+        assert!(duplicate.used_by.ends_with("ConfigError"));
+
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .context(ReadError::new("reading"))
+            .context(Uncoded::new("uncoded"))
+            .context(ConfigError::new("loading"))
+            .context(Uncoded::new("outer"))
+            .unwrap_err();
+
+        assert_eq!(
+            err.find_chain_cause::<ConfigError>().unwrap().code(),
+            "CFG-0012"
+        );
+        assert_eq!(codes.find_code(&err), Some("CFG-0012"));
+        assert_eq!(codes.root_code(&err), Some("IO-0001"));
+        assert_eq!(
+            codes.codes(&err).collect::<Vec<_>>(),
+            ["CFG-0012", "IO-0001"]
+        );
+        assert_eq!(codes.find_code(&io::Error::other("error")), None);
+
+        let wrapped = WrappedError::from(CodedKind::NotFound);
+        assert_eq!(wrapped.code(), "TST-0001");
+        assert_eq!(codes.find_code(&wrapped), Some("TST-0001"));
+        assert_eq!(
+            codes.find_code(&WrappedError::from(CodedKind::Invalid)),
+            Some("TST-0002")
+        );
+
+        let err = std::result::Result::<(), _>::Err(wrapped)
+            .context(Uncoded::new("outer"))
+            .unwrap_err();
+        assert_eq!(codes.find_code(&err), Some("TST-0001"));
+        assert_eq!(codes.root_code(&err), Some("TST-0001"));
+    }
+
+    #[test]
+    fn test_error_severity() {
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .context("reading")
+            .unwrap_err();
+        assert_eq!(err.severity(), None);
+
+        let err = Err::<(), _>(err.attach(Severity::Error))
+            .context("loading")
+            .attach(Severity::Warning)
+            .annotate()
+            .unwrap_err();
+
+        assert_eq!(err.severity(), Some(Severity::Error));
+        assert_eq!(
+            format!("{:#}", err),
+            "(passed error)\nCaused by:\n  warning: loading\nCaused by:\n  error: reading\nCaused by:\n  entity not found"
+        );
+        assert_eq!(format!("{}", err.source().unwrap()), "loading");
+        assert!(format!("{:#?}", err).contains("severity: Warning,"));
+
+        let err = Err::<(), _>(err)
+            .annotate()
+            .attach(Severity::Fatal)
+            .context("starting")
+            .unwrap_err();
+        assert_eq!(err.severity(), Some(Severity::Fatal));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_error_retryability() {
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::TimedOut))
+            .context("fetching")
+            .context("updating")
+            .unwrap_err();
+
+        let verdict = err.retryability().unwrap();
+        assert_eq!(verdict.retryability, Retryability::Transient);
+        assert_eq!(
+            verdict.layer.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::TimedOut
+        );
+        assert!(err.is_transient());
+        assert_eq!(err.source().unwrap().retryability(), None);
+
+        let err = Err::<(), _>(err.attach(Retryability::Permanent))
+            .annotate()
+            .unwrap_err();
+        let verdict = err.retryability().unwrap();
+        assert_eq!(verdict.retryability, Retryability::Permanent);
+        assert!(verdict.layer.is_chain::<&str>());
+        assert!(!err.is_transient());
+
+        let err = Error::from(io::ErrorKind::NotFound);
+        assert!(err
+            .retryability()
+            .unwrap()
+            .layer
+            .is_chain::<io::ErrorKind>());
+        assert_eq!(ErrorDown::retryability(&err), Some(Retryability::Permanent));
+        assert_eq!(
+            ErrorDown::retryability(&Error::from(io::ErrorKind::Other)),
+            None
+        );
+
+        let err = Error::from(io::ErrorKind::WouldBlock).classified();
+        assert_eq!(
+            err.attachment::<Retryability>(),
+            Some(&Retryability::Transient)
+        );
+        assert!(Error::new("unknown", None, None).retryability().is_none());
+    }
 
-        impl std::fmt::Display for $e {
-            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                std::fmt::Display::fmt(&self.0, f)
+    #[test]
+    fn test_classifiers() {
+        #[derive(Debug, Clone)]
+        enum DbError {
+            Deadlock,
+            Constraint,
+        }
+        impl Display for DbError {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{:?}", self)
             }
         }
-
-        impl std::fmt::Debug for $e {
-            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                std::fmt::Debug::fmt(&self.0, f)
+        impl Classify for DbError {
+            fn retryability(&self) -> Option<Retryability> {
+                match self {
+                    DbError::Deadlock => Some(Retryability::Transient),
+                    DbError::Constraint => None,
+                }
             }
         }
-    };
-}
+        err_kind!(WrappedDbError, DbError);
 
-#[cfg(test)]
-mod tests {
-    use super::Context as _;
-    use super::*;
-    use std::io;
+        let mut classifiers = Classifiers::new();
+        classifiers.register::<DbError>().register::<DbError>();
+        assert_eq!(classifiers.kinds.len(), 1);
+
+        let err = Err::<(), _>("deadlock detected")
+            .context(DbError::Deadlock)
+            .context("saving")
+            .unwrap_err();
+        assert!(err.retryability().is_none());
+        assert!(err.retryability_with(&Classifiers::new()).is_none());
+        let verdict = err.retryability_with(&classifiers).unwrap();
+        assert_eq!(verdict.retryability, Retryability::Transient);
+        assert!(verdict.layer.is_chain::<DbError>());
+
+        let err = Err::<(), _>(WrappedDbError::from(DbError::Deadlock))
+            .context("saving")
+            .unwrap_err();
+        let verdict = err.retryability_with(&classifiers).unwrap();
+        assert_eq!(verdict.retryability, Retryability::Transient);
+        assert!(verdict.layer.is::<WrappedDbError>());
+
+        let err = Err::<(), _>("constraint violated")
+            .context(DbError::Constraint)
+            .map_err(|e| e.attach(Retryability::Permanent))
+            .unwrap_err();
+        assert_eq!(
+            err.retryability_with(&classifiers).unwrap().retryability,
+            Retryability::Permanent
+        );
+        assert!(Err::<(), _>("constraint violated")
+            .context(DbError::Constraint)
+            .unwrap_err()
+            .retryability_with(&classifiers)
+            .is_none());
+    }
 
     #[test]
-    fn test_error_chain_with_multiple_causes() {
-        // Create a chain of errors
-        let io_error = io::Error::new(io::ErrorKind::NotFound, "file not found");
+    fn test_error_hints() {
+        str_context!(ReadError);
+        err_kind!(WrappedError, TestErrorKind);
 
-        str_context!(Level3Error);
-        str_context!(Level2Error);
-        str_context!(Level1Error);
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .annotate()
+            .note("the config is searched in the current directory")
+            .context(ReadError::new("Reading file: foo.toml"))
+            .help("run `tool init` to create a config")
+            .map_err(|e| {
+                WrappedError::from(
+                    Error::new(TestErrorKind::Basic("wrapped".into()), Some(e.into()), None)
+                        .note("second note"),
+                )
+            })
+            .context("loading")
+            .unwrap_err();
 
-        let err = Result::<(), _>::Err(io_error.into())
-            .context(Level3Error("level 3".into()))
-            .context(Level2Error("level 2".into()))
-            .context(Level1Error("level 1".into()))
+        assert_eq!(err.hints(), &[]);
+        assert_eq!(
+            format!("{:#}", err),
+            "loading\nCaused by:\n  Basic error: wrapped\nCaused by:\n  Reading file: foo.toml\n\
+             Caused by:\n  (passed error)\nCaused by:\n  entity not found\n\
+             note: the config is searched in the current directory\n\
+             help: run `tool init` to create a config\n\
+             note: second note"
+        );
+        assert_eq!(format!("{}", err), "loading");
+
+        let debug = format!("{:#?}", err);
+        assert!(debug.contains("hints: [\n"));
+        assert!(debug.contains("Help(\n"));
+    }
+
+    #[test]
+    fn test_error_size() {
+        #[cfg(feature = "thin")]
+        assert_eq!(
+            std::mem::size_of::<Error<TestErrorKind>>(),
+            std::mem::size_of::<usize>()
+        );
+        #[cfg(not(feature = "thin"))]
+        assert!(std::mem::size_of::<Error<TestErrorKind>>() > std::mem::size_of::<usize>());
+
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .context(TestErrorKind::Basic("inner".into()))
+            .map_err(|e| e.field("key", &1))
+            .context("outer")
             .unwrap_err();
+        assert_eq!(err.source().unwrap().fields(), &[Field::new("key", &1)]);
+        assert!(err.iter().any(|e| e.is_chain::<TestErrorKind>()));
+    }
 
-        // Test the error chain
-        assert!(err.is_chain::<Level1Error>());
-        assert!(err.find_chain_cause::<Level2Error>().is_some());
-        assert!(err.find_chain_cause::<Level3Error>().is_some());
-        assert!(err.find_chain_cause::<io::Error>().is_some());
+    #[test]
+    fn test_option_context() {
+        str_context!(MissingError);
+
+        assert_eq!(Some(1).context("missing").unwrap(), 1);
+        assert_eq!(
+            Some(1).with_context(|| -> &str { unreachable!() }).unwrap(),
+            1
+        );
+
+        #[cfg(not(chainerror_no_location))]
+        let line = line!() + 2;
+        let err = None::<u32>
+            .context(MissingError::new("no value"))
+            .unwrap_err();
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.line(), Some(line));
+        assert!(err.source().is_none());
+        assert_eq!(err.kind().0, "no value");
+
+        let err = None::<u32>
+            .with_context(|| format!("no value for {}", "key"))
+            .context("outer")
+            .unwrap_err();
+        assert_eq!(
+            err.source()
+                .unwrap()
+                .downcast_chain_ref::<String>()
+                .unwrap()
+                .kind(),
+            "no value for key"
+        );
+        assert_eq!(err.root_cause().unwrap().to_string(), "no value for key");
+
+        let err = None::<u32>.annotate().unwrap_err();
+        assert!(err.is_chain::<AnnotatedError>());
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.file(), Some(file!()));
     }
 
     #[test]
-    fn test_error_root_cause() {
-        let io_error = io::Error::new(io::ErrorKind::NotFound, "file not found");
+    fn test_iterator_context() {
+        #[cfg(not(chainerror_no_location))]
+        let line = line!() + 4;
+        let items = ["1", "x", "3", "y"]
+            .iter()
+            .map(|s| s.parse::<u32>())
+            .with_item_context(|index| format!("item #{}", index));
 
-        str_context!(WrapperError);
-        let err = Result::<(), _>::Err(io_error.into())
-            .context(WrapperError("wrapper".into()))
+        assert_eq!(items.size_hint(), (4, Some(4)));
+
+        let items = items.collect::<Vec<_>>();
+        assert_eq!(*items[0].as_ref().unwrap(), 1);
+        assert_eq!(*items[2].as_ref().unwrap(), 3);
+
+        let err = items[1].as_ref().unwrap_err();
+        assert_eq!(err.kind(), "item #1");
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.line(), Some(line));
+        assert!(err.find_cause::<core::num::ParseIntError>().is_some());
+
+        let err = items[3].as_ref().unwrap_err();
+        assert_eq!(err.kind(), "item #3");
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.line(), Some(line));
+    }
+
+    #[test]
+    fn test_collect_context() {
+        let files = ["a.txt", "b.txt", "c.txt", "d.txt"];
+        let read = |file: &&str| -> io::Result<usize> {
+            match *file {
+                "b.txt" => Err(io::ErrorKind::NotFound.into()),
+                "d.txt" => Err(io::ErrorKind::PermissionDenied.into()),
+                file => Ok(file.len()),
+            }
+        };
+
+        let sizes = files[..1]
+            .iter()
+            .map(read)
+            .collect_context::<Vec<_>, _, _>(|index| format!("reading {}", files[index]))
+            .unwrap();
+        assert_eq!(sizes, [5]);
+
+        #[cfg(not(chainerror_no_location))]
+        let line = line!() + 4;
+        let err = files
+            .iter()
+            .map(read)
+            .collect_context::<Vec<_>, _, _>(|index| format!("reading {}", files[index]))
             .unwrap_err();
+        assert_eq!(err.kind(), "reading b.txt");
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.line(), Some(line));
 
-        let root = err.root_cause().unwrap();
-        assert!(root.is_chain::<io::Error>());
+        #[cfg(not(chainerror_no_location))]
+        let line = line!() + 4;
+        let (sizes, errors) = files
+            .iter()
+            .map(read)
+            .partition_context::<Vec<_>, _, _>(|index| index);
+        assert_eq!(sizes, [5, 5]);
+        assert_eq!(errors.iter().map(|e| *e.kind()).collect::<Vec<_>>(), [1, 3]);
+        #[cfg(not(chainerror_no_location))]
+        assert!(errors.iter().all(|e| e.line() == Some(line)));
+        assert_eq!(
+            errors[1]
+                .source()
+                .unwrap()
+                .downcast_ref::<io::Error>()
+                .unwrap()
+                .kind(),
+            io::ErrorKind::PermissionDenied
+        );
     }
 
     #[test]
-    fn test_error_display_and_debug() {
-        str_context!(CustomError);
+    fn test_multi_error() {
+        str_context!(FieldError);
+
+        let field = |name: &str, kind: io::ErrorKind| -> Error<FieldError> {
+            Error::new(
+                FieldError::new(name),
+                Some(io::Error::from(kind).into()),
+                None,
+            )
+        };
+
+        let mut address = MultiError::new();
+        address.push(field("street", io::ErrorKind::InvalidInput));
+        address.push(field("zip", io::ErrorKind::InvalidData));
+
+        let mut multi = vec![field("name", io::ErrorKind::NotFound)]
+            .into_iter()
+            .collect::<MultiError>();
+        multi.push(Error::new("address", Some(address.into()), None));
+        assert_eq!(multi.len(), 2);
+        assert!(!multi.is_empty());
+        assert_eq!(MultiError::new().to_string(), "0 errors");
+
+        // `source()` is the first child
+        assert_eq!(multi.source().unwrap().to_string(), "name");
+
+        // Searches descend into every branch
+        assert_eq!(
+            multi.find_cause::<io::Error>().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(multi.find_chain_cause::<&str>().unwrap().kind(), &"address");
+
+        let err = Error::new("validating", Some(multi.into()), None);
+        let found = tree(&err)
+            .filter_map(|e| e.downcast_ref::<io::Error>())
+            .map(io::Error::kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                io::ErrorKind::NotFound,
+                io::ErrorKind::InvalidInput,
+                io::ErrorKind::InvalidData
+            ]
+        );
+        assert_eq!(
+            err.find_chain_cause::<FieldError>().unwrap().kind().0,
+            "name"
+        );
+        assert!(err.find_cause::<MultiError>().is_some());
+
+        assert_eq!(
+            format!("{:?}", err),
+            "\
+validating
+Caused by:
+2 errors
+├─ FieldError(name)
+│  Caused by:
+│  Kind(NotFound)
+└─ address
+   Caused by:
+   2 errors
+   ├─ FieldError(street)
+   │  Caused by:
+   │  Kind(InvalidInput)
+   └─ FieldError(zip)
+      Caused by:
+      Kind(InvalidData)"
+        );
+        assert!(format!("{:#?}", err).contains("MultiError {\n"));
+
+        // Attachments and suppressed errors are found in every branch
+        let mut multi = MultiError::new();
+        multi.push(field("name", io::ErrorKind::NotFound).attach(Severity::Warning));
+        multi.push(
+            field("email", io::ErrorKind::InvalidInput)
+                .attach(Severity::Fatal)
+                .suppress("cleanup failed"),
+        );
+        let err = Error::new("validating", Some(multi.into()), None);
+        assert_eq!(err.request::<Severity>(), Some(&Severity::Warning));
+        assert_eq!(
+            err.request_all::<Severity>().collect::<Vec<_>>(),
+            [&Severity::Warning, &Severity::Fatal]
+        );
+        assert_eq!(err.severity(), Some(Severity::Fatal));
+        assert_eq!(
+            err.suppressed().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["cleanup failed"]
+        );
+    }
+
+    #[test]
+    fn test_suppressed() {
         let err = Error::new(
-            CustomError("test error".into()),
+            "rolling back",
+            Some(io::Error::from(io::ErrorKind::TimedOut).into()),
             None,
-            Some("src/lib.rs:100".into()),
+        )
+        .suppress(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert!(format!("{:#?}", err).contains("suppressed: [\n"));
+
+        let err = Error::new("committing", Some(err.into()), None)
+            .suppress("closing the connection")
+            .suppress(Error::new("unlocking", None, None));
+
+        // The `source()` chain is unchanged
+        assert_eq!(err.iter().count(), 3);
+        assert_eq!(err.root_cause().unwrap().to_string(), "timed out");
+        assert_eq!(
+            err.find_cause::<io::Error>().unwrap().kind(),
+            io::ErrorKind::TimedOut
         );
 
-        // Test Display formatting
-        assert_eq!(format!("{}", err), "test error");
+        assert_eq!(
+            err.suppressed().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["closing the connection", "unlocking", "broken pipe"]
+        );
+        assert!(err.suppressed().nth(1).unwrap().is_chain::<&str>());
 
-        // Test alternate Display formatting
-        assert_eq!(format!("{:#}", err), "test error");
+        let debug = format!("{:#?}", err);
+        assert!(debug.contains("\"closing the connection\""));
+        assert!(debug.contains("BrokenPipe"));
+        assert!(!format!("{:?}", err).contains("closing the connection"));
+    }
 
-        // Test Debug formatting
-        let debug_output = format!("{:?}", err);
-        assert!(debug_output.contains("test error"));
-        assert!(debug_output.contains("src/lib.rs:100"));
+    #[test]
+    fn test_bail_ensure() {
+        #[derive(Clone, Debug)]
+        enum ParseKind {
+            Empty,
+            TooLong(usize),
+        }
+
+        impl Display for ParseKind {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    ParseKind::Empty => write!(f, "empty input"),
+                    ParseKind::TooLong(len) => write!(f, "input too long: {}", len),
+                }
+            }
+        }
+
+        err_kind!(ParseError, ParseKind);
+
+        #[cfg(not(chainerror_no_location))]
+        let base = line!();
+        fn parse(input: &str) -> core::result::Result<(), ParseError> {
+            ensure!(!input.is_empty(), ParseKind::Empty);
+            if input.len() > 3 {
+                bail!(ParseKind::TooLong(input.len()));
+            }
+            Ok(())
+        }
+
+        fn check(n: u32) -> core::result::Result<(), Box<dyn StdError + Send + Sync>> {
+            ensure!(n > 3, "{} is too small", n);
+            bail!("{n} is too big");
+        }
+
+        assert!(parse("abc").is_ok());
+        let err = parse("").unwrap_err();
+        assert!(matches!(err.kind(), ParseKind::Empty));
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.0.line(), Some(base + 2));
+        let err = parse("abcd").unwrap_err();
+        assert!(matches!(err.kind(), ParseKind::TooLong(4)));
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.0.line(), Some(base + 4));
+
+        let err = check(3).unwrap_err();
+        let err = err.downcast_chain_ref::<String>().unwrap();
+        assert_eq!(err.kind(), "3 is too small");
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.line(), Some(base + 10));
+
+        let err = check(4).unwrap_err();
+        let err = err.downcast_chain_ref::<String>().unwrap();
+        assert_eq!(err.kind(), "4 is too big");
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.line(), Some(base + 11));
+        assert!(err.source().is_none());
+
+        #[cfg(not(chainerror_no_location))]
+        let line = line!() + 1;
+        let err = format_context!("{}-{}", 1, 2).with_source(io::Error::from(io::ErrorKind::Other));
+        assert_eq!(err.kind(), "1-2");
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.line(), Some(line));
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.file(), Some(file!()));
+        assert!(err.find_cause::<io::Error>().is_some());
     }
 
     #[test]
@@ -812,6 +4485,35 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn test_with_context() {
+        let ok = std::result::Result::<_, io::Error>::Ok(1)
+            .with_context(|| -> String { unreachable!() })
+            .unwrap();
+        assert_eq!(ok, 1);
+
+        #[cfg(not(chainerror_no_location))]
+        let line = line!() + 2;
+        let err = std::result::Result::<(), _>::Err(io::Error::from(io::ErrorKind::NotFound))
+            .with_context(|| format!("reading {}", "foo.txt"))
+            .unwrap_err();
+        assert_eq!(err.kind(), "reading foo.txt");
+        #[cfg(not(chainerror_no_location))]
+        assert_eq!(err.line(), Some(line));
+        assert!(err.find_cause::<io::Error>().is_some());
+    }
+
+    #[test]
+    fn test_context_of_borrowed_error() {
+        fn parse(s: &str) -> Result<(), &'static str> {
+            Err::<(), &str>(s).context("parsing")
+        }
+
+        let input = String::from("invalid input");
+        let err = parse(&input).unwrap_err();
+        assert_eq!(err.source().unwrap().to_string(), "invalid input");
+    }
+
     #[test]
     fn test_map_context() {
         let io_error = io::Error::new(io::ErrorKind::NotFound, "file not found");
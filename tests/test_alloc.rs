@@ -0,0 +1,79 @@
+//! Counts the allocations of an error chain, see `cargo test --features thin --test test_alloc`
+// a captured backtrace allocates, depending on `RUST_BACKTRACE`
+#![cfg(not(feature = "backtrace"))]
+
+use chainerror::Context;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn chain() -> chainerror::Error<&'static str> {
+    let err: Result<(), _> = Err(io::Error::from(io::ErrorKind::NotFound));
+    err.context("1")
+        .context("2")
+        .context("3")
+        .context("4")
+        .context("5")
+        .context("6")
+        .unwrap_err()
+}
+
+#[test]
+fn test_alloc() {
+    // initialize the lazily captured environment, e.g. the current thread
+    drop(chain());
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let err = chain();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    // every layer boxes its error cause
+    let mut per_layer = 1;
+    // with `arc`, the `Box` of the error cause is moved to an `Arc`
+    if cfg!(feature = "arc") {
+        per_layer += 1;
+    }
+    // with `thin`, every layer is also a `Box` itself
+    if cfg!(feature = "thin") {
+        per_layer += 1;
+    }
+    // the captured environment is stored in the heap allocated layer with `thin`
+    if cfg!(any(
+        feature = "backtrace",
+        feature = "timestamp",
+        feature = "thread",
+        feature = "tracing"
+    )) && (cfg!(feature = "arc") || !cfg!(feature = "thin"))
+    {
+        per_layer += 1;
+    }
+
+    assert_eq!(err.iter().count(), 7);
+    assert_eq!(allocations, 6 * per_layer);
+
+    // searching the chain does not allocate
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    assert!(err.find_cause::<io::Error>().is_some());
+    assert!(err.find_chain_cause::<io::Error>().is_none());
+    assert!(err.find_kind_or_cause::<io::Error>().is_some());
+    assert!(err.request::<u32>().is_none());
+    assert_eq!(ALLOCATIONS.load(Ordering::Relaxed) - before, 0);
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "arc")]
+
+use chainerror::{Context, ErrorDown};
+use std::error::Error;
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+
+chainerror::str_context!(ReadError);
+
+fn read() -> chainerror::Result<(), ReadError> {
+    let err: Result<(), _> = Err(io::Error::from(io::ErrorKind::NotFound));
+    err.context("1").context(ReadError::new("2"))
+}
+
+#[test]
+fn test_clone() {
+    let err = read().unwrap_err();
+    let cloned = err.clone();
+
+    assert_eq!(format!("{:?}", err), format!("{:?}", cloned));
+    assert_eq!(format!("{:#}", err), format!("{:#}", cloned));
+    assert_eq!(cloned.kind().0, "2");
+    assert!(std::ptr::eq(
+        err.source().unwrap() as *const dyn Error as *const u8,
+        cloned.source().unwrap() as *const dyn Error as *const u8
+    ));
+
+    assert_eq!(cloned.iter().count(), 3);
+    assert!(cloned.is_chain::<ReadError>());
+    assert!(cloned
+        .source()
+        .unwrap()
+        .downcast_chain_ref::<&str>()
+        .is_some());
+    assert_eq!(
+        cloned.find_cause::<io::Error>().unwrap().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert_eq!(cloned.find_kind_or_cause::<ReadError>().unwrap().0, "2");
+}
+
+#[test]
+fn test_broadcast() {
+    let err = read().unwrap_err();
+    let (tx, rx) = mpsc::channel();
+
+    let waiters = (0..4)
+        .map(|_| {
+            let tx = tx.clone();
+            let err = err.clone();
+            thread::spawn(move || tx.send(format!("{:?}", err)).unwrap())
+        })
+        .collect::<Vec<_>>();
+    drop(tx);
+
+    for w in waiters {
+        w.join().unwrap();
+    }
+
+    for res in rx {
+        assert_eq!(res, format!("{:?}", err));
+    }
+}
+
+#[test]
+fn test_into_source_shared() {
+    let err = read().unwrap_err();
+    let cloned = err.clone();
+
+    let source = err.into_source().unwrap();
+    let source = source.downcast::<chainerror::Error<&str>>().unwrap_err();
+    assert_eq!(source.to_string(), "1");
+    drop(cloned);
+
+    let source = source.downcast::<chainerror::Error<&str>>().unwrap();
+    assert_eq!(*source.kind(), "1");
+    let io_error = source
+        .into_source()
+        .unwrap()
+        .downcast::<io::Error>()
+        .unwrap();
+    assert_eq!(io_error.kind(), io::ErrorKind::NotFound);
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "backtrace")]
+
+use chainerror::{Context, ErrorDown};
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::error::Error;
+use std::io;
+
+#[test]
+fn test_backtrace() {
+    let captured = Backtrace::capture().status() == BacktraceStatus::Captured;
+
+    let err: Result<(), _> = Err(io::Error::from(io::ErrorKind::NotFound));
+    let err = err.context("1").context("2").unwrap_err();
+
+    assert_eq!(
+        err.backtrace().status() == BacktraceStatus::Captured,
+        captured
+    );
+    assert_eq!(
+        err.source()
+            .unwrap()
+            .downcast_chain_ref::<&str>()
+            .unwrap()
+            .backtrace()
+            .status()
+            == BacktraceStatus::Captured,
+        captured
+    );
+    assert_eq!(format!("{:#?}", err).contains("backtrace: "), captured);
+    assert!(!format!("{:?}", err).contains("backtrace: "));
+}
@@ -22,8 +22,10 @@ fn test_basic() {
         let s = format!("{:?}", e);
         let lines = s.lines().collect::<Vec<_>>();
         assert_eq!(lines.len(), 5);
+        #[cfg(not(chainerror_no_location))]
         assert!(lines[0].starts_with("tests/test_basic.rs:"));
         assert_eq!(lines[1], "Caused by:");
+        #[cfg(not(chainerror_no_location))]
         assert!(lines[2].starts_with("tests/test_basic.rs:"));
         assert_eq!(lines[3], "Caused by:");
         assert_eq!(lines[4], format!("{:?}", os_notfound_error));
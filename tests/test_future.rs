@@ -0,0 +1,85 @@
+#![cfg(feature = "future")]
+
+use chainerror::{ErrorDown, FutureContext};
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// A minimal executor, which parks the thread until the future is woken
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// Returns `Pending` once, before it resolves to `result`
+async fn yield_once<O>(result: Result<O, io::Error>) -> Result<O, io::Error> {
+    let mut yielded = false;
+    futures_util::future::poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await;
+    result
+}
+
+#[test]
+fn test_future() {
+    #[cfg(not(chainerror_no_location))]
+    let line = line!() + 2;
+    let connect =
+        yield_once::<()>(Err(io::ErrorKind::ConnectionRefused.into())).context("connecting");
+    let read = yield_once(Ok(42)).with_context(|| -> String { unreachable!() });
+    let annotated = yield_once::<()>(Err(io::ErrorKind::TimedOut.into())).annotate();
+
+    let (connect, read, annotated) =
+        block_on(async { (connect.await, read.await, annotated.await) });
+
+    let err = connect.unwrap_err();
+    #[cfg(not(chainerror_no_location))]
+    assert_eq!(err.line(), Some(line));
+    assert!(err.find_cause::<io::Error>().is_some());
+    assert_eq!(read.unwrap(), 42);
+
+    let err = annotated.unwrap_err();
+    assert!(err.is_chain::<chainerror::AnnotatedError>());
+    assert_eq!(
+        err.source()
+            .unwrap()
+            .downcast_inner_ref::<io::Error>()
+            .unwrap()
+            .kind(),
+        io::ErrorKind::TimedOut
+    );
+
+    let err = block_on(
+        yield_once::<()>(Err(io::ErrorKind::NotFound.into()))
+            .with_context(|| format!("reading {}", "foo.txt")),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), "reading foo.txt");
+}
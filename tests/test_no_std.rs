@@ -0,0 +1,79 @@
+//! Checks the `no_std` build with `cargo test --no-default-features --test test_no_std`
+#![no_std]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use chainerror::{Context, ErrorDown};
+use core::error::Error;
+use core::fmt;
+
+#[derive(Debug)]
+struct IoError;
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "io error")
+    }
+}
+
+impl Error for IoError {}
+
+chainerror::str_context!(ReadError);
+
+#[derive(Debug, Clone)]
+enum ErrorKind {
+    Config,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "config error")
+    }
+}
+
+chainerror::err_kind!(ConfigError, ErrorKind);
+
+fn read() -> Result<(), Box<dyn Error + Send + Sync>> {
+    Err(IoError)?;
+    Ok(())
+}
+
+fn read_config() -> Result<(), ConfigError> {
+    read()
+        .context(ReadError::new("reading config"))
+        .annotate()
+        .context(ErrorKind::Config)?;
+    Ok(())
+}
+
+#[test]
+fn test_no_std() {
+    let err = read_config().unwrap_err();
+
+    assert!(matches!(err.kind(), ErrorKind::Config));
+    assert_eq!(format!("{}", err), "config error");
+
+    let source = err.source().unwrap();
+    assert!(source.is_chain::<chainerror::AnnotatedError>());
+
+    let chain = source
+        .downcast_chain_ref::<chainerror::AnnotatedError>()
+        .unwrap();
+    assert!(chain.find_cause::<IoError>().is_some());
+    assert!(chain.find_chain_cause::<ReadError>().is_some());
+    assert!(chain.root_cause().unwrap().is::<IoError>());
+
+    let mut res = String::new();
+    for e in chain.iter() {
+        res.push_str(&format!("{}|", e));
+    }
+    assert_eq!(res, "(passed error)|reading config|io error|");
+
+    let debug = format!("{:?}", chain);
+    #[cfg(not(chainerror_no_location))]
+    assert!(debug.starts_with("tests/test_no_std.rs:"));
+    assert!(debug.ends_with("Caused by:\nIoError"));
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "stream")]
+
+use chainerror::StreamContext;
+use futures_executor::block_on;
+use futures_util::{stream, Stream as _, StreamExt as _};
+use std::error::Error as _;
+use std::io;
+
+#[test]
+fn test_stream() {
+    let records = vec![
+        Ok(1),
+        Err(io::Error::from(io::ErrorKind::InvalidData)),
+        Ok(3),
+        Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+    ];
+
+    #[cfg(not(chainerror_no_location))]
+    let line = line!() + 2;
+    let items = stream::iter(records)
+        .with_item_context(|index| format!("record #{} of stream \"orders\"", index));
+    assert_eq!(items.size_hint(), (4, Some(4)));
+
+    let items = block_on(items.collect::<Vec<_>>());
+    assert_eq!(items.len(), 4);
+    assert_eq!(*items[0].as_ref().unwrap(), 1);
+    assert_eq!(*items[2].as_ref().unwrap(), 3);
+
+    let err = items[1].as_ref().unwrap_err();
+    assert_eq!(err.kind(), "record #1 of stream \"orders\"");
+    #[cfg(not(chainerror_no_location))]
+    assert_eq!(err.line(), Some(line));
+    assert_eq!(
+        err.source()
+            .unwrap()
+            .downcast_ref::<io::Error>()
+            .unwrap()
+            .kind(),
+        io::ErrorKind::InvalidData
+    );
+
+    let err = items[3].as_ref().unwrap_err();
+    assert_eq!(err.kind(), "record #3 of stream \"orders\"");
+    assert!(err.find_cause::<io::Error>().is_some());
+}
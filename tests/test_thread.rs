@@ -0,0 +1,29 @@
+#![cfg(feature = "thread")]
+
+use chainerror::{Context, ErrorDown};
+use std::error::Error;
+use std::io;
+use std::thread;
+
+#[test]
+fn test_thread() {
+    let err = thread::Builder::new()
+        .name("worker-1".into())
+        .spawn(|| Err::<(), _>(io::Error::from(io::ErrorKind::NotFound)).context("reading"))
+        .unwrap()
+        .join()
+        .unwrap();
+
+    let err = err.context("handing off").unwrap_err();
+
+    let inner = err.source().unwrap().downcast_chain_ref::<&str>().unwrap();
+
+    assert_eq!(err.thread_id(), thread::current().id());
+    assert_eq!(err.thread_name(), thread::current().name());
+    assert_ne!(inner.thread_id(), err.thread_id());
+    assert_eq!(inner.thread_name(), Some("worker-1"));
+
+    let debug = format!("{:#?}", err);
+    assert!(debug.contains("\"worker-1\""));
+    assert!(debug.contains("thread: Thread {"));
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "timestamp")]
+
+use chainerror::{Context, ErrorDown};
+use std::error::Error;
+use std::io;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_timestamp() {
+    let start = SystemTime::now();
+
+    let err: Result<(), _> = Err(io::Error::from(io::ErrorKind::TimedOut));
+    let err = err.context("attempt 1");
+    thread::sleep(Duration::from_millis(20));
+    let err = err.context("attempt 2").unwrap_err();
+
+    let inner = err.source().unwrap().downcast_chain_ref::<&str>().unwrap();
+
+    assert!(inner.timestamp() >= start);
+    assert!(err.timestamp() >= inner.timestamp());
+    assert!(err.instant().duration_since(inner.instant()) >= Duration::from_millis(20));
+
+    let debug = format!("{:#?}", err);
+    let elapsed = debug
+        .lines()
+        .filter(|l| l.trim_start().starts_with("elapsed: +"))
+        .collect::<Vec<_>>();
+    // the innermost `io::Error` has no timestamp
+    assert_eq!(elapsed.len(), 1);
+    assert!(elapsed[0].ends_with("ms,"));
+}
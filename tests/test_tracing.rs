@@ -0,0 +1,103 @@
+#![cfg(feature = "tracing")]
+
+use chainerror::Context;
+use std::fmt::Debug;
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_error::ErrorLayer;
+use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt as _};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+#[derive(Default)]
+struct Fields(Vec<(String, String)>);
+
+impl Fields {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+struct Recorded {
+    level: Level,
+    span: Option<String>,
+    fields: Fields,
+}
+
+/// Records the level, the name of the span and the fields of every event
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<Recorded>>>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push((field.name().into(), format!("{:?}", value)));
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let span = ctx.event_span(event).map(|span| span.name().to_string());
+        self.0.lock().unwrap().push(Recorded {
+            level: *event.metadata().level(),
+            span,
+            fields,
+        });
+    }
+}
+
+#[test]
+fn test_tracing() {
+    let recorder = Recorder::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(ErrorLayer::default())
+        .with(recorder.clone());
+
+    let err = tracing::subscriber::with_default(subscriber, || {
+        let err = tracing::info_span!("connect", host = "db").in_scope(|| {
+            Err::<(), _>(io::Error::from(io::ErrorKind::ConnectionRefused)).context("connecting")
+        });
+        let err = err.context("starting").unwrap_err();
+
+        err.trace(Level::WARN);
+        err
+    });
+
+    let events = recorder.0.lock().unwrap();
+    assert_eq!(events.len(), 3);
+
+    for (index, event) in events.iter().enumerate() {
+        assert_eq!(event.level, Level::WARN);
+        assert_eq!(event.span.as_deref(), Some("error_chain"));
+        assert_eq!(event.fields.get("index"), Some(index.to_string().as_str()));
+    }
+
+    let fields = &events[0].fields;
+    assert_eq!(fields.get("error"), Some("starting"));
+    assert_eq!(fields.get("kind"), Some("\"starting\""));
+    #[cfg(not(chainerror_no_location))]
+    assert!(fields.get("occurrence").unwrap().starts_with(file!()));
+
+    let fields = &events[1].fields;
+    assert_eq!(fields.get("error"), Some("connecting"));
+    assert_eq!(fields.get("kind"), Some("\"connecting\""));
+    #[cfg(not(chainerror_no_location))]
+    assert!(fields.get("occurrence").unwrap().starts_with(file!()));
+
+    let fields = &events[2].fields;
+    assert_eq!(fields.get("error"), Some("connection refused"));
+    assert_eq!(fields.get("kind"), None);
+    assert_eq!(fields.get("occurrence"), None);
+
+    let inner = std::error::Error::source(&err).unwrap();
+    let inner = chainerror::ErrorDown::downcast_chain_ref::<&str>(inner).unwrap();
+    assert!(format!("{}", inner.span_trace()).contains("connect"));
+    assert!(!format!("{}", err.span_trace()).contains("connect"));
+    assert!(format!("{:#?}", inner).contains("span_trace: "));
+}